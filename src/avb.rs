@@ -0,0 +1,97 @@
+use byteorder::{BigEndian, ReadBytesExt};
+use image::source_len;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Read, Seek, SeekFrom};
+
+/// The size of the AVB footer, in bytes. The footer is stored in the last bytes
+/// of the partition image.
+pub const AVB_FOOTER_SIZE: usize = 64;
+/// The magic at the start of the AVB footer.
+const AVB_FOOTER_MAGIC: &[u8; 4] = b"AVBf";
+
+/// The footer Android Verified Boot appends to partition images. Unlike the
+/// boot image header, its fields are stored big-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AvbFooter {
+    /// The major version of the footer format.
+    pub version_major: u32,
+    /// The minor version of the footer format.
+    pub version_minor: u32,
+    /// The size of the image before the AVB data was appended, in bytes.
+    pub original_image_size: u64,
+    /// The offset of the vbmeta blob, in bytes.
+    pub vbmeta_offset: u64,
+    /// The size of the vbmeta blob, in bytes.
+    pub vbmeta_size: u64,
+}
+
+impl AvbFooter {
+    /// Reads the AVB footer from the end of a source. Returns `None` when the
+    /// source does not end in an AVB footer.
+    pub fn read_from<R: Read + Seek>(source: &mut R) -> Result<Option<Self>, IoError> {
+        if source_len(source)? < AVB_FOOTER_SIZE as u64 {
+            return Ok(None);
+        }
+
+        source.seek(SeekFrom::End(-(AVB_FOOTER_SIZE as i64)))?;
+        let mut magic = [0; 4];
+        source.read_exact(&mut magic)?;
+        if &magic != AVB_FOOTER_MAGIC {
+            return Ok(None);
+        }
+
+        Ok(Some(AvbFooter {
+            version_major: source.read_u32::<BigEndian>()?,
+            version_minor: source.read_u32::<BigEndian>()?,
+            original_image_size: source.read_u64::<BigEndian>()?,
+            vbmeta_offset: source.read_u64::<BigEndian>()?,
+            vbmeta_size: source.read_u64::<BigEndian>()?,
+        }))
+    }
+}
+
+/// Reads an image, leaving out the AVB data appended to it. When the image
+/// ends in an AVB footer, only the first `original_image_size` bytes are
+/// returned. Otherwise the image is returned unchanged.
+pub fn strip_avb<R: Read + Seek>(source: &mut R) -> Result<Vec<u8>, IoError> {
+    let length = match AvbFooter::read_from(source)? {
+        Some(footer) => {
+            if footer.original_image_size > source_len(source)? {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidData,
+                    "the AVB footer describes an image larger than the file",
+                ));
+            }
+            footer.original_image_size
+        }
+        None => source_len(source)?,
+    };
+
+    let mut output = Vec::with_capacity(length as usize);
+    source.seek(SeekFrom::Start(0))?;
+    source.take(length).read_to_end(&mut output)?;
+    Ok(output)
+}
+
+/// Reads the vbmeta blob of an image that ends in an AVB footer, which can be
+/// inspected with `avbtool`. Returns `None` when the image does not end in an
+/// AVB footer.
+pub fn extract_vbmeta<R: Read + Seek>(source: &mut R) -> Result<Option<Vec<u8>>, IoError> {
+    let footer = match AvbFooter::read_from(source)? {
+        Some(footer) => footer,
+        None => return Ok(None),
+    };
+
+    let length = source_len(source)?;
+    let end = footer.vbmeta_offset.checked_add(footer.vbmeta_size);
+    if !matches!(end, Some(end) if end <= length) {
+        return Err(IoError::new(
+            IoErrorKind::InvalidData,
+            "the AVB footer describes a vbmeta blob outside of the file",
+        ));
+    }
+
+    let mut output = Vec::with_capacity(footer.vbmeta_size as usize);
+    source.seek(SeekFrom::Start(footer.vbmeta_offset))?;
+    source.take(footer.vbmeta_size).read_to_end(&mut output)?;
+    Ok(Some(output))
+}
@@ -0,0 +1,230 @@
+use {Endianness, Header, SectionKind, SectionOrder};
+use image::{HeaderSummary, ReadBootImageError, SectionSummary, check_header, check_page_size,
+            section_offset, section_size, size_to_size_in_pages};
+use std::fmt;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+/// A boot image whose sections borrow from the buffer it was parsed from. Only
+/// the header is copied, which makes this suitable for processing large images
+/// that are already in memory, for example a mapped file.
+///
+/// Like `BootImage`, the `Debug` output does not dump the sections.
+#[derive(Clone, PartialEq, Eq)]
+pub struct BorrowedBootImage<'a> {
+    /// The header of this boot image.
+    header: Header,
+    /// The kernel.
+    kernel: &'a [u8],
+    /// The ramdisk.
+    ramdisk: &'a [u8],
+    /// The optional second file. This is usually a second ramdisk, but can
+    /// also be a second stage bootloader.
+    second: &'a [u8],
+    /// The device tree.
+    device_tree: &'a [u8],
+}
+
+impl<'a> BorrowedBootImage<'a> {
+    /// Parses a boot image from a buffer containing the entire image.
+    ///
+    /// As some boot images have their page size set to 0, an override page
+    /// size can be supplied. If the header size is set to 0, and no valid
+    /// override is supplied, this function will return an error. An invalid
+    /// page size is rejected like `BootImage::read_from` does.
+    pub fn from_slice(
+        source: &'a [u8],
+        override_page_size: Option<u32>,
+    ) -> Result<Self, ReadBootImageError> {
+        BorrowedBootImage::from_slice_endian(source, override_page_size, Endianness::default())
+    }
+
+    /// Parses a boot image from a buffer containing the entire image, like
+    /// `from_slice`, with the header fields in the given byte order.
+    pub fn from_slice_endian(
+        source: &'a [u8],
+        override_page_size: Option<u32>,
+        endianness: Endianness,
+    ) -> Result<Self, ReadBootImageError> {
+        let mut header = Header::read_from_endian(&mut &source[..], endianness)?;
+        check_page_size(&header, override_page_size)?;
+        header.page_size = override_page_size.unwrap_or(header.page_size);
+        let header = check_header(header)?;
+
+        let mut boot_image = BorrowedBootImage {
+            header,
+            kernel: &[],
+            ramdisk: &[],
+            second: &[],
+            device_tree: &[],
+        };
+
+        // The offset of each section depends on the size of the previous one,
+        // so the sections have to be sliced out in order.
+        boot_image.kernel = section(
+            source,
+            boot_image.kernel_offset(),
+            boot_image.header.kernel_size,
+        )?;
+        boot_image.ramdisk = section(
+            source,
+            boot_image.ramdisk_offset(),
+            boot_image.header.ramdisk_size,
+        )?;
+        boot_image.second = section(
+            source,
+            boot_image.second_ramdisk_offset(),
+            boot_image.header.second_size,
+        )?;
+        boot_image.device_tree = section(
+            source,
+            boot_image.device_tree_offset(),
+            boot_image.header.device_tree_size,
+        )?;
+
+        Ok(boot_image)
+    }
+
+    /// Returns a reference to the header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the size of a single page.
+    pub fn page_size(&self) -> usize {
+        self.header.page_size as usize
+    }
+
+    /// Returns a reference to the kernel.
+    pub fn kernel(&self) -> &'a [u8] {
+        self.kernel
+    }
+
+    /// Returns a reference to the ramdisk.
+    pub fn ramdisk(&self) -> &'a [u8] {
+        self.ramdisk
+    }
+
+    /// Returns a reference to the second ramdisk.
+    pub fn second_ramdisk(&self) -> &'a [u8] {
+        self.second
+    }
+
+    /// Returns a reference to the device tree.
+    pub fn device_tree(&self) -> &'a [u8] {
+        self.device_tree
+    }
+
+    /// Returns how many pages the header is big.
+    pub fn header_size_in_pages(&self) -> usize {
+        size_to_size_in_pages(::std::mem::size_of::<Header>(), self.page_size())
+    }
+
+    /// Returns how many pages the kernel is big.
+    pub fn kernel_size_in_pages(&self) -> usize {
+        self.section_size_in_pages(SectionKind::Kernel)
+    }
+
+    /// Returns how many pages the ramdisk is big.
+    pub fn ramdisk_size_in_pages(&self) -> usize {
+        self.section_size_in_pages(SectionKind::Ramdisk)
+    }
+
+    /// Returns how many pages the second ramdisk is big.
+    pub fn second_ramdisk_size_in_pages(&self) -> usize {
+        self.section_size_in_pages(SectionKind::Second)
+    }
+
+    /// Returns how many pages the device tree is big.
+    pub fn device_tree_size_in_pages(&self) -> usize {
+        self.section_size_in_pages(SectionKind::DeviceTree)
+    }
+
+    /// Returns the offset to the header, in pages.
+    pub fn header_offset_in_pages(&self) -> usize {
+        0
+    }
+
+    /// Returns the offset to the kernel, in pages.
+    pub fn kernel_offset_in_pages(&self) -> usize {
+        self.section_offset_in_pages(SectionKind::Kernel)
+    }
+
+    /// Returns the offset to the ramdisk, in pages.
+    pub fn ramdisk_offset_in_pages(&self) -> usize {
+        self.section_offset_in_pages(SectionKind::Ramdisk)
+    }
+
+    /// Returns the offset to the second ramdisk, in pages.
+    pub fn second_ramdisk_offset_in_pages(&self) -> usize {
+        self.section_offset_in_pages(SectionKind::Second)
+    }
+
+    /// Returns the offset to the device tree, in pages.
+    pub fn device_tree_offset_in_pages(&self) -> usize {
+        self.section_offset_in_pages(SectionKind::DeviceTree)
+    }
+
+    /// Returns the offset to the header, in bytes.
+    pub fn header_offset(&self) -> usize {
+        self.header_offset_in_pages() * self.page_size()
+    }
+
+    /// Returns the offset to the kernel, in bytes.
+    pub fn kernel_offset(&self) -> usize {
+        self.kernel_offset_in_pages() * self.page_size()
+    }
+
+    /// Returns the offset to the ramdisk, in bytes.
+    pub fn ramdisk_offset(&self) -> usize {
+        self.ramdisk_offset_in_pages() * self.page_size()
+    }
+
+    /// Returns the offset to the second ramdisk, in bytes.
+    pub fn second_ramdisk_offset(&self) -> usize {
+        self.second_ramdisk_offset_in_pages() * self.page_size()
+    }
+
+    /// Returns the offset to the device tree, in bytes.
+    pub fn device_tree_offset(&self) -> usize {
+        self.device_tree_offset_in_pages() * self.page_size()
+    }
+
+    /// Helper function to get the size of a section following the header, in
+    /// pages, as described by the header.
+    fn section_size_in_pages(&self, section: SectionKind) -> usize {
+        size_to_size_in_pages(section_size(&self.header, section), self.page_size())
+    }
+
+    /// Helper function to get the offset to a section following the header, in
+    /// pages. The offsets are computed like `BootImage` computes them, with the
+    /// sections in the standard order.
+    fn section_offset_in_pages(&self, section: SectionKind) -> usize {
+        section_offset(&self.header, &SectionOrder::STANDARD, section) / self.page_size()
+    }
+}
+
+impl<'a> fmt::Debug for BorrowedBootImage<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BorrowedBootImage")
+            .field("header", &HeaderSummary(&self.header))
+            .field("kernel", &SectionSummary(self.kernel))
+            .field("ramdisk", &SectionSummary(self.ramdisk))
+            .field("second", &SectionSummary(self.second))
+            .field("device_tree", &SectionSummary(self.device_tree))
+            .finish()
+    }
+}
+
+/// Helper function to slice a section out of the source buffer. Fails when the
+/// buffer ends before the section does.
+fn section(source: &[u8], offset: usize, size: u32) -> Result<&[u8], IoError> {
+    offset
+        .checked_add(size as usize)
+        .and_then(|end| source.get(offset..end))
+        .ok_or_else(|| {
+            IoError::new(
+                IoErrorKind::UnexpectedEof,
+                "the boot image ends before the section does",
+            )
+        })
+}
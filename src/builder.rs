@@ -0,0 +1,103 @@
+use header::{Header, MAGIC_SIZE};
+use image::{BadHeaderError, BootImage};
+
+/// A builder used to construct a boot image from scratch. All values that are
+/// not supplied are taken from the default header.
+#[derive(Debug, Clone, Default)]
+pub struct BootImageBuilder {
+    /// The header that will be inserted into the boot image.
+    header: Header,
+    /// The kernel.
+    kernel: Vec<u8>,
+    /// The ramdisk.
+    ramdisk: Vec<u8>,
+    /// The kernel command line, if one was supplied.
+    cmdline: Option<String>,
+    /// The product name, if one was supplied.
+    product_name: Option<String>,
+}
+
+impl BootImageBuilder {
+    /// Creates a new builder, starting out with the default header and no
+    /// sections.
+    pub fn new() -> Self {
+        BootImageBuilder::default()
+    }
+
+    /// Sets the kernel of the boot image.
+    pub fn kernel(mut self, kernel: Vec<u8>) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    /// Sets the ramdisk of the boot image.
+    pub fn ramdisk(mut self, ramdisk: Vec<u8>) -> Self {
+        self.ramdisk = ramdisk;
+        self
+    }
+
+    /// Sets the magic of the boot image, for OEMs that do not use the
+    /// 'ANDROID!' magic.
+    pub fn magic(mut self, magic: [u8; MAGIC_SIZE]) -> Self {
+        self.header.magic = magic;
+        self
+    }
+
+    /// Sets the page size of the boot image.
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.header.page_size = page_size;
+        self
+    }
+
+    /// Sets the address the kernel should be loaded to.
+    pub fn kernel_load_address(mut self, address: u32) -> Self {
+        self.header.kernel_load_address = address;
+        self
+    }
+
+    /// Sets the arguments to pass to the kernel during boot.
+    pub fn cmdline(mut self, cmdline: &str) -> Self {
+        self.cmdline = Some(cmdline.into());
+        self
+    }
+
+    /// Sets the name of the product.
+    pub fn product_name(mut self, product_name: &str) -> Self {
+        self.product_name = Some(product_name.into());
+        self
+    }
+
+    /// Builds the boot image. The section sizes in the header are computed from
+    /// the supplied sections.
+    ///
+    /// This function fails when the page size is 0 or not one used by Android
+    /// devices, see `is_valid_page_size`, or when the product name or command
+    /// line do not fit in the header. The magic is not checked, as it is the
+    /// one chosen with `magic`.
+    pub fn build(self) -> Result<BootImage, BadHeaderError> {
+        let mut header = self.header;
+
+        if let Some(ref product_name) = self.product_name {
+            if header.set_product_name(product_name).is_err() {
+                return Err(BadHeaderError::ProductNameTooLong(header));
+            }
+        }
+        if let Some(ref cmdline) = self.cmdline {
+            if header.set_cmdline(cmdline).is_err() {
+                return Err(BadHeaderError::CmdlineTooLong(header));
+            }
+        }
+
+        if header.page_size != 0 && !header.has_valid_page_size() {
+            return Err(BadHeaderError::InvalidPageSize(header));
+        }
+
+        let mut boot_image = BootImage::default();
+        // Only the page size is left to check, the magic is the chosen one.
+        let magic = header.magic;
+        boot_image.insert_header_with_magic(header, &magic)?;
+        boot_image.insert_kernel(self.kernel);
+        boot_image.insert_ramdisk(self.ramdisk);
+        Ok(boot_image)
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(feature = "decompress")]
+use std::io::{Error as IoError, Read, Write};
+
+/// A compression format sections are commonly stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Not compressed, or compressed in an unknown format.
+    None,
+    /// gzip, like an `Image.gz` kernel.
+    Gzip,
+    /// lz4, in the frame format.
+    Lz4,
+    /// lz4, in the legacy format the kernel uses.
+    Lz4Legacy,
+    /// xz.
+    Xz,
+    /// lzma.
+    Lzma,
+    /// bzip2.
+    Bzip2,
+}
+
+impl Compression {
+    /// Detects how data is compressed by looking at its magic.
+    pub fn detect(data: &[u8]) -> Self {
+        const MAGICS: &[(&[u8], Compression)] = &[
+            (b"\x1F\x8B", Compression::Gzip),
+            (b"\x02\x21\x4C\x18", Compression::Lz4Legacy),
+            (b"\x04\x22\x4D\x18", Compression::Lz4),
+            (b"\xFD7zXZ\x00", Compression::Xz),
+            (b"\x5D\x00\x00", Compression::Lzma),
+            (b"BZh", Compression::Bzip2),
+        ];
+
+        MAGICS
+            .iter()
+            .find(|&&(magic, _)| data.starts_with(magic))
+            .map_or(Compression::None, |&(_, compression)| compression)
+    }
+}
+
+/// Decompresses data, detecting its compression with `Compression::detect`.
+/// Data that is not compressed is returned as-is. Only gzip is supported, other
+/// formats cause an error of the `InvalidInput` kind.
+#[cfg(feature = "decompress")]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, IoError> {
+    use flate2::read::GzDecoder;
+    use std::io::ErrorKind as IoErrorKind;
+
+    match Compression::detect(data) {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut output = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut output)?;
+            Ok(output)
+        }
+        compression => Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            format!("{:?} compressed data cannot be decompressed", compression),
+        )),
+    }
+}
+
+/// Compresses data in the given format, the inverse of `decompress`. Data is
+/// returned as-is for `Compression::None`. Only gzip is supported, other formats
+/// cause an error of the `InvalidInput` kind.
+#[cfg(feature = "decompress")]
+pub fn compress(data: &[u8], compression: Compression) -> Result<Vec<u8>, IoError> {
+    use flate2::Compression as GzLevel;
+    use flate2::write::GzEncoder;
+    use std::io::ErrorKind as IoErrorKind;
+
+    match compression {
+        Compression::None => Ok(data.to_vec()),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), GzLevel::best());
+            encoder.write_all(data)?;
+            encoder.finish()
+        }
+        compression => Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            format!("data cannot be compressed with {:?}", compression),
+        )),
+    }
+}
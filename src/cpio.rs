@@ -0,0 +1,186 @@
+use std::io::Error as IoError;
+
+/// The magic of a newc cpio entry header.
+const NEWC_MAGIC: &[u8; 6] = b"070701";
+/// The magic of a newc cpio entry header that has a checksum of the contents.
+const NEWC_CRC_MAGIC: &[u8; 6] = b"070702";
+/// The size of a newc cpio entry header, in bytes.
+const NEWC_HEADER_SIZE: usize = 110;
+/// The index of the size field of a newc header.
+const SIZE_FIELD: usize = 6;
+/// The index of the checksum field of a newc header.
+const CHECK_FIELD: usize = 12;
+/// The name of the entry marking the end of a cpio archive.
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// An entry of a cpio archive, without its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpioEntry {
+    /// The path of the entry, relative to the root of the archive.
+    pub name: String,
+    /// The file type and permissions, as in `st_mode`.
+    pub mode: u32,
+    /// The size of the contents, in bytes.
+    pub size: u32,
+}
+
+/// Parses the entries of a cpio archive in the newc format, as used for
+/// ramdisks. The archive has to be decompressed already. Parsing stops at the
+/// trailer entry, which is not returned.
+pub fn parse_cpio(archive: &[u8]) -> Result<Vec<CpioEntry>, CpioError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while let Some(location) = read_entry(archive, offset)? {
+        offset = location.end;
+        entries.push(location.entry);
+    }
+    Ok(entries)
+}
+
+/// Replaces the contents of the file at the given path in a cpio archive in the
+/// newc format, returning the new archive. The size, and the checksum when the
+/// archive has them, are updated; all the other entries are kept as-is.
+pub fn replace_cpio_file(
+    archive: &[u8],
+    path: &str,
+    contents: &[u8],
+) -> Result<Vec<u8>, CpioError> {
+    if contents.len() > u32::MAX as usize {
+        return Err(CpioError::FileTooLarge(contents.len()));
+    }
+
+    let mut output = Vec::with_capacity(archive.len() + contents.len());
+    let mut offset = 0;
+    let mut replaced = false;
+    while let Some(location) = read_entry(archive, offset)? {
+        if location.entry.name == path && !replaced {
+            let mut header = archive[offset..location.data_start].to_vec();
+            write_hex_field(&mut header, SIZE_FIELD, contents.len() as u32);
+            if header.starts_with(NEWC_CRC_MAGIC) {
+                let check = contents
+                    .iter()
+                    .fold(0u32, |check, &byte| check.wrapping_add(u32::from(byte)));
+                write_hex_field(&mut header, CHECK_FIELD, check);
+            }
+
+            output.extend_from_slice(&header);
+            output.extend_from_slice(contents);
+            output.resize(align_to_4(output.len()), 0);
+            replaced = true;
+        } else {
+            output.extend_from_slice(&archive[offset..location.end]);
+        }
+        offset = location.end;
+    }
+    if !replaced {
+        return Err(CpioError::FileNotFound(path.to_owned()));
+    }
+
+    // Keep the trailer, and any padding after it, as-is.
+    output.extend_from_slice(&archive[offset..]);
+    Ok(output)
+}
+
+/// Where an entry is stored in a cpio archive.
+struct EntryLocation {
+    entry: CpioEntry,
+    /// The offset of the contents of the entry.
+    data_start: usize,
+    /// The offset of the next entry.
+    end: usize,
+}
+
+/// Helper function to read the entry at the given offset of a newc archive.
+/// Returns `None` for the trailer entry.
+fn read_entry(archive: &[u8], offset: usize) -> Result<Option<EntryLocation>, CpioError> {
+    let header = archive
+        .get(offset..offset + NEWC_HEADER_SIZE)
+        .ok_or(CpioError::Truncated)?;
+    if !header.starts_with(NEWC_MAGIC) && !header.starts_with(NEWC_CRC_MAGIC) {
+        return Err(CpioError::BadMagic(offset));
+    }
+
+    let mode = read_hex_field(header, 1)?;
+    let size = read_hex_field(header, SIZE_FIELD)?;
+    let name_size = read_hex_field(header, 11)? as usize;
+
+    let name_start = offset + NEWC_HEADER_SIZE;
+    let name = archive
+        .get(name_start..name_start + name_size)
+        .ok_or(CpioError::Truncated)?;
+    let name = String::from_utf8_lossy(name.split(|&byte| byte == 0).next().unwrap());
+    if name == TRAILER_NAME {
+        return Ok(None);
+    }
+
+    let data_start = align_to_4(name_start + name_size);
+    let end = align_to_4(data_start + size as usize);
+    if data_start + size as usize > archive.len() {
+        return Err(CpioError::Truncated);
+    }
+    Ok(Some(EntryLocation {
+        entry: CpioEntry {
+            name: name.into_owned(),
+            mode,
+            size,
+        },
+        data_start,
+        end: end.min(archive.len()),
+    }))
+}
+
+/// Helper function to read one of the hexadecimal fields of a newc header,
+/// by its index. The magic is not counted as a field.
+fn read_hex_field(header: &[u8], index: usize) -> Result<u32, CpioError> {
+    let start = 6 + index * 8;
+    ::std::str::from_utf8(&header[start..start + 8])
+        .ok()
+        .and_then(|field| u32::from_str_radix(field, 16).ok())
+        .ok_or(CpioError::BadField(index))
+}
+
+/// Helper function to write one of the hexadecimal fields of a newc header, by
+/// its index. See `read_hex_field`.
+fn write_hex_field(header: &mut [u8], index: usize, value: u32) {
+    let start = 6 + index * 8;
+    header[start..start + 8].copy_from_slice(format!("{:08X}", value).as_bytes());
+}
+
+/// Helper function to round an offset up to the next multiple of 4, as newc
+/// archives pad names and contents to 4 bytes.
+fn align_to_4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum CpioError {
+        Io(cause: IoError) {
+            description("The ramdisk could not be decompressed or compressed")
+            display("The ramdisk could not be decompressed or compressed.")
+            cause(cause)
+            from(cause: IoError) -> (cause)
+        }
+        BadMagic(offset: usize) {
+            description("A cpio entry does not start with the newc magic")
+            display("The cpio entry at offset {} does not start with the newc magic.", offset)
+        }
+        BadField(index: usize) {
+            description("A cpio entry header contains an invalid field")
+            display("Field {} of a cpio entry header is not a hexadecimal number.", index)
+        }
+        Truncated {
+            description("The cpio archive is truncated")
+            display("The cpio archive is truncated.")
+        }
+        FileNotFound(path: String) {
+            description("The file is not in the cpio archive")
+            display("The file '{}' is not in the cpio archive.", path)
+        }
+        FileTooLarge(size: usize) {
+            description("The file is too large for a cpio archive")
+            display("The file is {} bytes large, but cpio archives only support files \
+                     up to 4 GiB.", size)
+        }
+    }
+}
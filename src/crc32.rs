@@ -0,0 +1,50 @@
+/// The lookup table for the CRC-32 (IEEE 802.3) polynomial, in its reflected
+/// form.
+const TABLE: [u32; 256] = make_table();
+
+/// Helper function to generate the lookup table at compile time.
+const fn make_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 == 1 {
+                (value >> 1) ^ 0xEDB88320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+}
+
+/// A running CRC-32 computation.
+pub(crate) struct Crc32 {
+    /// The current, inverted, checksum.
+    state: u32,
+}
+
+impl Crc32 {
+    /// Starts a new computation.
+    pub fn new() -> Self {
+        Crc32 { state: 0xFFFFFFFF }
+    }
+
+    /// Adds data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let index = ((self.state ^ byte as u32) & 0xFF) as usize;
+            self.state = (self.state >> 8) ^ TABLE[index];
+        }
+    }
+
+    /// Returns the checksum of all the data added so far.
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
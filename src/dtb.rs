@@ -0,0 +1,45 @@
+use byteorder::{BigEndian, ByteOrder};
+
+/// The magic at the start of a flattened device tree blob (FDT).
+const FDT_MAGIC: [u8; 4] = [0xd0, 0x0d, 0xfe, 0xed];
+/// The size of the part of the FDT header holding the magic and total size.
+const FDT_MIN_SIZE: usize = 8;
+
+/// An iterator over the flattened device tree blobs (FDT) in a device tree
+/// section, as found in images that simply concatenate them. Every blob is
+/// found by scanning for the FDT magic, and its length is read from its header.
+/// Magics whose total size does not fit in the section are skipped.
+#[derive(Debug, Clone)]
+pub struct DtbIter<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> DtbIter<'a> {
+    /// Creates an iterator over the device tree blobs in a device tree section.
+    pub fn new(device_tree: &'a [u8]) -> Self {
+        DtbIter { remaining: device_tree }
+    }
+}
+
+impl<'a> Iterator for DtbIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        while let Some(start) = self.remaining.windows(4).position(|window| window == FDT_MAGIC) {
+            let candidate = &self.remaining[start..];
+            let total_size = candidate
+                .get(4..FDT_MIN_SIZE)
+                .map(|size| BigEndian::read_u32(size) as usize)
+                .unwrap_or(0);
+
+            if total_size >= FDT_MIN_SIZE && total_size <= candidate.len() {
+                self.remaining = &candidate[total_size..];
+                return Some(&candidate[..total_size]);
+            }
+            self.remaining = &candidate[1..];
+        }
+
+        self.remaining = &[];
+        None
+    }
+}
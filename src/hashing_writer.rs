@@ -0,0 +1,59 @@
+use std::hash::Hasher;
+use std::io::{Result as IoResult, Write};
+
+/// A writer that passes everything written to it on to another writer, while
+/// also feeding it to a hasher. This allows computing a digest of a boot image
+/// while writing it.
+#[derive(Debug, Clone)]
+pub struct HashingWriter<W: Write, H: Hasher> {
+    /// The writer the data is passed on to.
+    writer: W,
+    /// The hasher the data is fed to.
+    hasher: H,
+}
+
+impl<W: Write, H: Hasher> HashingWriter<W, H> {
+    /// Wraps a writer and a hasher.
+    pub fn new(writer: W, hasher: H) -> Self {
+        HashingWriter { writer, hasher }
+    }
+
+    /// Returns a reference to the wrapped writer.
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a reference to the wrapped hasher.
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Unwraps this writer, returning the wrapped writer and hasher.
+    pub fn into_inner(self) -> (W, H) {
+        (self.writer, self.hasher)
+    }
+}
+
+impl<W: Write, H: Hasher> Write for HashingWriter<W, H> {
+    /// Writes to the wrapped writer. Only the bytes it accepted are hashed.
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.write(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write, H: Hasher> Hasher for HashingWriter<W, H> {
+    fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+
+    /// Feeds bytes to the wrapped hasher only, without writing them.
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+    }
+}
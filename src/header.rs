@@ -1,18 +1,35 @@
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use SectionKind;
+use image::{BadHeaderError, ReadBootImageError};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
 use std::io::{Error as IoError, Read, Write};
 
 /// The size of the header, in bytes. This might not match up with the
 /// amount of bytes the structure consumes while in memory.
 pub const HEADER_SIZE: usize = 616;
-const MAGIC: [u8; MAGIC_SIZE] = [0x41, 0x4E, 0x44, 0x52, 0x4F, 0x49, 0x44, 0x21];
-pub const MAGIC_STR: &'static str = "ANDROID!";
-const MAGIC_SIZE: usize = 8;
-const PRODUCT_NAME_SIZE: usize = 24;
-const BOOT_ARGUMENTS_SIZE: usize = 512;
+/// The magic a header starts with.
+pub const MAGIC: [u8; MAGIC_SIZE] = [0x41, 0x4E, 0x44, 0x52, 0x4F, 0x49, 0x44, 0x21];
+pub const MAGIC_STR: &str = "ANDROID!";
+pub const MAGIC_SIZE: usize = 8;
+pub(crate) const PRODUCT_NAME_SIZE: usize = 24;
+pub(crate) const BOOT_ARGUMENTS_SIZE: usize = 512;
 const UNIQUE_ID_SIZE: usize = 32;
+/// The value of the reserved field in the default header, as found in Samsung
+/// boot images.
+pub(crate) const DEFAULT_RESERVED: u32 = 0x02000000;
 
 /// Contains a magic header.
-#[derive(Debug, Clone)]
+///
+/// With the `serde` feature enabled, the header can be serialized. The product
+/// name and boot arguments are represented as strings, the magic and unique id
+/// as hexadecimal strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "serde_support::HeaderRepr"))]
+#[cfg_attr(feature = "serde", serde(try_from = "serde_support::HeaderRepr"))]
 pub struct Header {
     /// Header magic. Used to make sure this is in fact a header.
     pub magic: [u8; MAGIC_SIZE],
@@ -33,7 +50,10 @@ pub struct Header {
 
     /// The size of the device tree, in bytes.
     pub device_tree_size: u32,
-    /// Room for future expansion. This should always be set to 0.
+    /// Room for future expansion. Samsung images do not leave this set to 0,
+    /// the default header uses `0x02000000`, and on some devices it encodes
+    /// the size of extra data. It is therefore kept as-is when reading and
+    /// writing. See `reserved_raw`.
     _reserved: u32,
 
     /// Physical address of the kernel tags.
@@ -46,13 +66,28 @@ pub struct Header {
     /// rust does not allow us to have arrays larger than 32 in size.
     pub boot_arguments: [[u8; BOOT_ARGUMENTS_SIZE / 16]; 16],
     /// Used to uniquely identify boot images.
-    pub unique_id: [u8; UNIQUE_ID_SIZE],
+    pub unique_id: UniqueId,
 }
 
 impl Header {
     /// Reads a header from the supplied source. This does not perform the
     /// magic check, and as a result cannot error.
     pub fn parse(source: &[u8; HEADER_SIZE]) -> Self {
+        Header::parse_endian(source, Endianness::default())
+    }
+
+    /// Reads a header whose integers are stored with the given endianness from
+    /// the supplied source. This does not perform the magic check, and as a
+    /// result cannot error.
+    pub fn parse_endian(source: &[u8; HEADER_SIZE], endianness: Endianness) -> Self {
+        match endianness {
+            Endianness::Little => Header::parse_with::<LittleEndian>(source),
+            Endianness::Big => Header::parse_with::<BigEndian>(source),
+        }
+    }
+
+    /// Helper function implementing `parse_endian` for a single byte order.
+    fn parse_with<B: ByteOrder>(source: &[u8; HEADER_SIZE]) -> Self {
         let mut source = &source[..];
 
         Header {
@@ -61,16 +96,16 @@ impl Header {
                 source.read_exact(&mut buffer).unwrap();
                 buffer
             },
-            kernel_size: source.read_u32::<LittleEndian>().unwrap(),
-            kernel_load_address: source.read_u32::<LittleEndian>().unwrap(),
-            ramdisk_size: source.read_u32::<LittleEndian>().unwrap(),
-            ramdisk_load_address: source.read_u32::<LittleEndian>().unwrap(),
-            second_size: source.read_u32::<LittleEndian>().unwrap(),
-            second_load_address: source.read_u32::<LittleEndian>().unwrap(),
-            device_tree_size: source.read_u32::<LittleEndian>().unwrap(),
-            _reserved: source.read_u32::<LittleEndian>().unwrap(),
-            kernel_tags_address: source.read_u32::<LittleEndian>().unwrap(),
-            page_size: source.read_u32::<LittleEndian>().unwrap(),
+            kernel_size: source.read_u32::<B>().unwrap(),
+            kernel_load_address: source.read_u32::<B>().unwrap(),
+            ramdisk_size: source.read_u32::<B>().unwrap(),
+            ramdisk_load_address: source.read_u32::<B>().unwrap(),
+            second_size: source.read_u32::<B>().unwrap(),
+            second_load_address: source.read_u32::<B>().unwrap(),
+            device_tree_size: source.read_u32::<B>().unwrap(),
+            _reserved: source.read_u32::<B>().unwrap(),
+            kernel_tags_address: source.read_u32::<B>().unwrap(),
+            page_size: source.read_u32::<B>().unwrap(),
             product_name: {
                 let mut buffer = [0; PRODUCT_NAME_SIZE];
                 source.read_exact(&mut buffer).unwrap();
@@ -80,36 +115,63 @@ impl Header {
                 use std::mem::transmute;
                 let mut buffer = [0; BOOT_ARGUMENTS_SIZE];
                 source.read_exact(&mut buffer).unwrap();
-                transmute(buffer)
+                transmute::<[u8; BOOT_ARGUMENTS_SIZE], [[u8; BOOT_ARGUMENTS_SIZE / 16]; 16]>(buffer)
             },
             unique_id: {
                 let mut buffer = [0u8; UNIQUE_ID_SIZE];
                 source.read_exact(&mut buffer).unwrap();
-                buffer
+                UniqueId(buffer)
             },
         }
     }
 
     pub fn read_from<R: Read>(source: &mut R) -> Result<Self, IoError> {
+        Header::read_from_endian(source, Endianness::default())
+    }
+
+    /// Reads a header whose integers are stored with the given endianness from
+    /// a `Read` source.
+    pub fn read_from_endian<R: Read>(
+        source: &mut R,
+        endianness: Endianness,
+    ) -> Result<Self, IoError> {
         let mut buffer = [0; HEADER_SIZE];
         source.read_exact(&mut buffer)?;
-        Ok(Header::parse(&buffer))
+        Ok(Header::parse_endian(&buffer, endianness))
     }
 
     /// Writes this header to a `Write` target. Returns the amount of bytes
     /// written.
     pub fn write_to<W: Write>(&self, target: &mut W) -> Result<usize, IoError> {
+        self.write_to_endian(target, Endianness::default())
+    }
+
+    /// Writes this header to a `Write` target, storing its integers with the
+    /// given endianness. Returns the amount of bytes written.
+    pub fn write_to_endian<W: Write>(
+        &self,
+        target: &mut W,
+        endianness: Endianness,
+    ) -> Result<usize, IoError> {
+        match endianness {
+            Endianness::Little => self.write_with::<LittleEndian, W>(target),
+            Endianness::Big => self.write_with::<BigEndian, W>(target),
+        }
+    }
+
+    /// Helper function implementing `write_to_endian` for a single byte order.
+    fn write_with<B: ByteOrder, W: Write>(&self, target: &mut W) -> Result<usize, IoError> {
         target.write_all(&self.magic)?;
-        target.write_u32::<LittleEndian>(self.kernel_size)?;
-        target.write_u32::<LittleEndian>(self.kernel_load_address)?;
-        target.write_u32::<LittleEndian>(self.ramdisk_size)?;
-        target.write_u32::<LittleEndian>(self.ramdisk_load_address)?;
-        target.write_u32::<LittleEndian>(self.second_size)?;
-        target.write_u32::<LittleEndian>(self.second_load_address)?;
-        target.write_u32::<LittleEndian>(self.device_tree_size)?;
-        target.write_u32::<LittleEndian>(self._reserved)?;
-        target.write_u32::<LittleEndian>(self.kernel_tags_address)?;
-        target.write_u32::<LittleEndian>(self.page_size)?;
+        target.write_u32::<B>(self.kernel_size)?;
+        target.write_u32::<B>(self.kernel_load_address)?;
+        target.write_u32::<B>(self.ramdisk_size)?;
+        target.write_u32::<B>(self.ramdisk_load_address)?;
+        target.write_u32::<B>(self.second_size)?;
+        target.write_u32::<B>(self.second_load_address)?;
+        target.write_u32::<B>(self.device_tree_size)?;
+        target.write_u32::<B>(self._reserved)?;
+        target.write_u32::<B>(self.kernel_tags_address)?;
+        target.write_u32::<B>(self.page_size)?;
         target.write_all(&self.product_name)?;
         for ii in self.boot_arguments.iter() {
             target.write_all(ii)?;
@@ -121,6 +183,360 @@ impl Header {
     pub fn has_correct_magic(&self) -> bool {
         self.magic == MAGIC_STR.as_bytes()
     }
+
+    /// Returns whether this header starts with the given magic instead of the
+    /// 'ANDROID!' one. Some OEMs use a different magic for their images.
+    pub fn has_magic(&self, magic: &[u8; MAGIC_SIZE]) -> bool {
+        self.magic == *magic
+    }
+
+    /// Returns whether this header equals another one, ignoring the unique id,
+    /// the reserved field and the padding after the product name and command
+    /// line. These are the fields the `Hash` implementation skips.
+    pub fn logical_eq(&self, other: &Header) -> bool {
+        self.magic == other.magic
+            && self.kernel_size == other.kernel_size
+            && self.kernel_load_address == other.kernel_load_address
+            && self.ramdisk_size == other.ramdisk_size
+            && self.ramdisk_load_address == other.ramdisk_load_address
+            && self.second_size == other.second_size
+            && self.second_load_address == other.second_load_address
+            && self.device_tree_size == other.device_tree_size
+            && self.kernel_tags_address == other.kernel_tags_address
+            && self.page_size == other.page_size
+            && c_string_bytes(&self.product_name) == c_string_bytes(&other.product_name)
+            && c_string_bytes(self.boot_arguments_bytes())
+                == c_string_bytes(other.boot_arguments_bytes())
+    }
+
+    /// Returns the magic this header starts with.
+    pub fn magic_bytes(&self) -> &[u8; MAGIC_SIZE] {
+        &self.magic
+    }
+
+    /// Returns whether both headers have the same product name. Only the bytes
+    /// up to the null terminator are compared, so padding is ignored.
+    pub fn same_product(&self, other: &Header) -> bool {
+        c_string_bytes(&self.product_name) == c_string_bytes(&other.product_name)
+    }
+
+    /// Returns whether both headers have the same command line. Only the bytes
+    /// up to the null terminator are compared, so padding is ignored.
+    pub fn same_cmdline(&self, other: &Header) -> bool {
+        c_string_bytes(self.boot_arguments_bytes())
+            == c_string_bytes(other.boot_arguments_bytes())
+    }
+
+    /// Returns the unique id of the image as a lowercase hexadecimal string.
+    pub fn unique_id_hex(&self) -> String {
+        to_hex(&self.unique_id)
+    }
+
+    /// Returns the unique id of the image.
+    pub fn unique_id(&self) -> UniqueId {
+        self.unique_id
+    }
+
+    /// Returns the name of the product. Invalid UTF-8 is replaced. When the
+    /// name is not null-terminated, the whole field is returned.
+    pub fn product_name_str(&self) -> String {
+        read_c_string(&self.product_name)
+    }
+
+    /// Returns the command line field as a single array, instead of the rows it
+    /// is stored in.
+    pub(crate) fn boot_arguments_bytes(&self) -> &[u8; BOOT_ARGUMENTS_SIZE] {
+        // The rows are stored back to back, so they have the layout of a
+        // single array.
+        unsafe { &*(self.boot_arguments.as_ptr() as *const [u8; BOOT_ARGUMENTS_SIZE]) }
+    }
+
+    /// Returns the command line field as a single mutable array. See
+    /// `boot_arguments_bytes`.
+    pub(crate) fn boot_arguments_bytes_mut(&mut self) -> &mut [u8; BOOT_ARGUMENTS_SIZE] {
+        unsafe { &mut *(self.boot_arguments.as_mut_ptr() as *mut [u8; BOOT_ARGUMENTS_SIZE]) }
+    }
+
+    /// Returns the arguments to pass to the kernel during boot. Invalid UTF-8 is
+    /// replaced. When the command line is not null-terminated, the whole field
+    /// is returned.
+    pub fn cmdline(&self) -> String {
+        read_c_string(self.boot_arguments_bytes())
+    }
+
+    /// Sets the arguments to pass to the kernel during boot. The command line
+    /// is null-terminated, so it has to be shorter than 512 bytes.
+    pub fn set_cmdline(&mut self, cmdline: &str) -> Result<(), HeaderFieldError> {
+        if write_c_string(self.boot_arguments_bytes_mut(), cmdline) {
+            Ok(())
+        } else {
+            Err(HeaderFieldError::CmdlineTooLong(cmdline.len()))
+        }
+    }
+
+    /// Sets the name of the product. The name is null-terminated, so it has to
+    /// be shorter than 24 bytes.
+    pub fn set_product_name(&mut self, product_name: &str) -> Result<(), HeaderFieldError> {
+        if write_c_string(&mut self.product_name, product_name) {
+            Ok(())
+        } else {
+            Err(HeaderFieldError::ProductNameTooLong(product_name.len()))
+        }
+    }
+
+    /// Returns the raw value of the reserved field.
+    ///
+    /// While the field is meant to be 0, Samsung images use it. The default
+    /// header sets it to `0x02000000`, and on some devices it holds the size of
+    /// the device tree or other extra data. Headers that are read keep their
+    /// original value, so it survives a read-modify-write cycle.
+    pub fn reserved_raw(&self) -> u32 {
+        self._reserved
+    }
+
+    /// Sets the raw value of the reserved field. See `reserved_raw`.
+    pub fn set_reserved_raw(&mut self, value: u32) {
+        self._reserved = value;
+    }
+
+    /// Returns the size of the device tree as stored in the reserved field by
+    /// some Samsung devices. Returns `None` when the header has a device tree
+    /// size set, or when the reserved field is 0 or holds the `0x02000000` of
+    /// the default header.
+    pub fn dt_size_from_reserved(&self) -> Option<u32> {
+        if self.device_tree_size == 0 && self._reserved != 0 && self._reserved != DEFAULT_RESERVED {
+            Some(self._reserved)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the page size is one used by Android devices. See
+    /// `is_valid_page_size`.
+    pub fn has_valid_page_size(&self) -> bool {
+        is_valid_page_size(self.page_size)
+    }
+
+    /// Sets the address the kernel should be loaded to.
+    pub fn set_kernel_load_address(&mut self, address: u32) {
+        self.kernel_load_address = address;
+    }
+
+    /// Sets the address the ramdisk should be loaded to.
+    pub fn set_ramdisk_load_address(&mut self, address: u32) {
+        self.ramdisk_load_address = address;
+    }
+
+    /// Sets the address the optional second file should be loaded to.
+    pub fn set_second_load_address(&mut self, address: u32) {
+        self.second_load_address = address;
+    }
+
+    /// Sets the physical address of the kernel tags.
+    pub fn set_kernel_tags_address(&mut self, address: u32) {
+        self.kernel_tags_address = address;
+    }
+
+    /// Returns the load addresses of the kernel, the ramdisk and the second
+    /// file that are not aligned to the page size, with their section. Returns
+    /// nothing when the page size is 0.
+    pub fn unaligned_load_addresses(&self) -> Vec<(SectionKind, u32)> {
+        if self.page_size == 0 {
+            return Vec::new();
+        }
+
+        vec![
+            (SectionKind::Kernel, self.kernel_load_address),
+            (SectionKind::Ramdisk, self.ramdisk_load_address),
+            (SectionKind::Second, self.second_load_address),
+        ].into_iter()
+            .filter(|&(_, address)| address % self.page_size != 0)
+            .collect()
+    }
+
+    /// Returns whether the load addresses of the kernel, the ramdisk and the
+    /// second file are aligned to the page size, as bootloaders require.
+    pub fn addresses_aligned(&self) -> bool {
+        self.unaligned_load_addresses().is_empty()
+    }
+
+    /// Rounds the load addresses of the kernel, the ramdisk and the second file
+    /// down to the page size. Does nothing when the page size is 0.
+    pub fn align_addresses(&mut self) {
+        if self.page_size == 0 {
+            return;
+        }
+
+        let page_size = self.page_size;
+        for address in &mut [
+            &mut self.kernel_load_address,
+            &mut self.ramdisk_load_address,
+            &mut self.second_load_address,
+        ] {
+            **address -= **address % page_size;
+        }
+    }
+
+    /// Returns the address the kernel starts executing at, which is the address
+    /// it is loaded to.
+    pub fn entry_point(&self) -> u64 {
+        u64::from(self.kernel_load_address)
+    }
+
+    /// Returns the memory region the ramdisk is loaded into, as its address and
+    /// size in bytes.
+    pub fn ramdisk_region(&self) -> (u64, u64) {
+        (u64::from(self.ramdisk_load_address), u64::from(self.ramdisk_size))
+    }
+
+    /// Sets the sizes of all the sections to 0.
+    pub fn reset_sizes(&mut self) {
+        self.kernel_size = 0;
+        self.ramdisk_size = 0;
+        self.second_size = 0;
+        self.device_tree_size = 0;
+    }
+
+    /// Returns a template of this header, which can be filled in with new
+    /// sections. It has the correct magic, and keeps the addresses, page size,
+    /// product name and command line, but all the section sizes are 0 and the
+    /// unique id is cleared.
+    pub fn header_template(&self) -> Header {
+        Header {
+            kernel_load_address: self.kernel_load_address,
+            ramdisk_load_address: self.ramdisk_load_address,
+            second_load_address: self.second_load_address,
+            _reserved: self._reserved,
+            kernel_tags_address: self.kernel_tags_address,
+            page_size: self.page_size,
+            product_name: self.product_name,
+            boot_arguments: self.boot_arguments,
+            ..Header::default()
+        }
+    }
+
+    /// Returns the `mkbootimg` arguments that reproduce the addresses, page
+    /// size and command line of this header.
+    ///
+    /// The base address is the smallest of the load addresses and the kernel
+    /// tags address, rounded down to a 64 KiB boundary, and all the addresses
+    /// are given as offsets from it. The header has no OS version, so
+    /// `--os_version` is never emitted.
+    pub fn to_mkbootimg_args(&self) -> Vec<String> {
+        let addresses = [
+            self.kernel_load_address,
+            self.ramdisk_load_address,
+            self.second_load_address,
+            self.kernel_tags_address,
+        ];
+        let base = addresses.iter().min().unwrap() & !0xffff;
+
+        vec![
+            "--base".to_owned(),
+            format!("0x{:08x}", base),
+            "--kernel_offset".to_owned(),
+            format!("0x{:08x}", self.kernel_load_address - base),
+            "--ramdisk_offset".to_owned(),
+            format!("0x{:08x}", self.ramdisk_load_address - base),
+            "--second_offset".to_owned(),
+            format!("0x{:08x}", self.second_load_address - base),
+            "--tags_offset".to_owned(),
+            format!("0x{:08x}", self.kernel_tags_address - base),
+            "--pagesize".to_owned(),
+            self.page_size.to_string(),
+            "--cmdline".to_owned(),
+            self.cmdline(),
+        ]
+    }
+
+    /// Sets all the load addresses, and the kernel tags address, to the
+    /// standard ones for the given SoC family.
+    pub fn apply_defaults_for(&mut self, soc: SocProfile) {
+        let (base, second_offset) = match soc {
+            SocProfile::Exynos => (0x10000000, 0x000f0000),
+            SocProfile::Msm => (0x80000000, 0x00f00000),
+        };
+
+        self.set_kernel_load_address(base + 0x00008000);
+        self.set_ramdisk_load_address(base + 0x01000000);
+        self.set_second_load_address(base + second_offset);
+        self.set_kernel_tags_address(base + 0x00000100);
+    }
+}
+
+/// The id used to uniquely identify boot images, usually the id computed by
+/// `BootImage::compute_id`. It dereferences to its bytes, and is formatted as
+/// hexadecimal with `{:x}` and `{:X}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct UniqueId(pub [u8; UNIQUE_ID_SIZE]);
+
+impl Deref for UniqueId {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for UniqueId {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl fmt::LowerHex for UniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl fmt::UpperHex for UniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02X}", byte))
+    }
+}
+
+/// Returns whether a page size is one used by Android devices: a power of two
+/// from 2048 up to 16384 bytes. Other page sizes are almost always the result of
+/// a corrupt header or a wrong override.
+pub fn is_valid_page_size(page_size: u32) -> bool {
+    page_size.is_power_of_two() && (2048..=16384).contains(&page_size)
+}
+
+/// The byte order the integers of a header are stored in. Android devices use
+/// little-endian headers, but some ports to big-endian devices do not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// Least significant byte first. This is the default.
+    #[default]
+    Little,
+    /// Most significant byte first.
+    Big,
+}
+
+/// A family of SoCs sharing the same standard load addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocProfile {
+    /// Samsung Exynos SoCs. These are the addresses of the default header.
+    Exynos,
+    /// Qualcomm MSM and APQ SoCs.
+    Msm,
+}
+
+impl<'a> TryFrom<&'a [u8]> for Header {
+    type Error = ReadBootImageError;
+
+    /// Parses a header from the start of a buffer. Bytes after the header are
+    /// ignored. Fails when the buffer is too short to contain a header, or when
+    /// the header does not have the valid magic.
+    fn try_from(source: &'a [u8]) -> Result<Self, ReadBootImageError> {
+        let header = Header::read_from(&mut &source[..])?;
+        if header.has_correct_magic() {
+            Ok(header)
+        } else {
+            Err(BadHeaderError::BadMagic(header).into())
+        }
+    }
 }
 
 impl Default for Header {
@@ -134,12 +550,249 @@ impl Default for Header {
             second_size: 0,
             second_load_address: 0x100f0000,
             device_tree_size: 0,
-            _reserved: 0x02000000,
+            _reserved: DEFAULT_RESERVED,
             kernel_tags_address: 0x10000100,
             page_size: 2048,
             product_name: [0; PRODUCT_NAME_SIZE],
             boot_arguments: [[0; 32]; 16],
-            unique_id: [0; UNIQUE_ID_SIZE],
+            unique_id: UniqueId::default(),
+        }
+    }
+}
+
+impl Hash for Header {
+    /// Hashes the magic, the sizes, the addresses, the page size, the product
+    /// name and the command line. The unique id and the reserved field are
+    /// skipped, so headers that only differ in those hash equally. The padding
+    /// after the product name and command line is skipped too.
+    ///
+    /// As `Eq` does compare every field, a `HashSet<Header>` still keeps such
+    /// headers apart. Use `LogicalHeader` to group them.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.magic.hash(state);
+        self.kernel_size.hash(state);
+        self.kernel_load_address.hash(state);
+        self.ramdisk_size.hash(state);
+        self.ramdisk_load_address.hash(state);
+        self.second_size.hash(state);
+        self.second_load_address.hash(state);
+        self.device_tree_size.hash(state);
+        self.kernel_tags_address.hash(state);
+        self.page_size.hash(state);
+        c_string_bytes(&self.product_name).hash(state);
+        c_string_bytes(self.boot_arguments_bytes()).hash(state);
+    }
+}
+
+/// A header that compares and hashes only the fields hashed by the `Hash`
+/// implementation of `Header`. Headers that only differ in their unique id or
+/// reserved field are equal, so a `HashSet<LogicalHeader>` groups them.
+#[derive(Debug, Clone)]
+pub struct LogicalHeader(pub Header);
+
+impl PartialEq for LogicalHeader {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.logical_eq(&other.0)
+    }
+}
+
+impl Eq for LogicalHeader {}
+
+impl Hash for LogicalHeader {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl fmt::Display for Header {
+    /// Formats the header as a human readable summary, one field per line.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let magic = if self.has_correct_magic() { "valid" } else { "invalid" };
+
+        writeln!(f, "Magic:                 {}", magic)?;
+        writeln!(f, "Page size:             {}", self.page_size)?;
+        writeln!(f, "Kernel size:           {}", self.kernel_size)?;
+        writeln!(f, "Kernel load address:   0x{:08X}", self.kernel_load_address)?;
+        writeln!(f, "Ramdisk size:          {}", self.ramdisk_size)?;
+        writeln!(f, "Ramdisk load address:  0x{:08X}", self.ramdisk_load_address)?;
+        writeln!(f, "Second size:           {}", self.second_size)?;
+        writeln!(f, "Second load address:   0x{:08X}", self.second_load_address)?;
+        writeln!(f, "Device tree size:      {}", self.device_tree_size)?;
+        writeln!(f, "Kernel tags address:   0x{:08X}", self.kernel_tags_address)?;
+        writeln!(f, "Product name:          {}", read_c_string(&self.product_name))?;
+        writeln!(f, "Unique id:             {}", self.unique_id_hex())?;
+        write!(
+            f,
+            "Command line:          {}",
+            self.cmdline()
+        )
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum HeaderFieldError {
+        CmdlineTooLong(length: usize) {
+            description("The kernel command line does not fit in the header")
+            display("The kernel command line is {} bytes long, but at most {} bytes fit.",
+                    length, BOOT_ARGUMENTS_SIZE - 1)
+        }
+        ProductNameTooLong(length: usize) {
+            description("The product name does not fit in the header")
+            display("The product name is {} bytes long, but at most {} bytes fit.",
+                    length, PRODUCT_NAME_SIZE - 1)
+        }
+    }
+}
+
+/// Formats bytes as a lowercase hexadecimal string.
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Returns the bytes of a null-terminated string in a fixed size field, without
+/// the terminator. When there is no terminator, the whole field is used.
+fn c_string_bytes(field: &[u8]) -> &[u8] {
+    let length = field.iter().position(|&byte| byte == 0).unwrap_or(field.len());
+    &field[..length]
+}
+
+/// Sets all the bytes after the terminator of a null-terminated string in a
+/// fixed size field to 0.
+pub(crate) fn clear_c_string_padding(field: &mut [u8]) {
+    let length = c_string_bytes(field).len();
+    for byte in &mut field[length..] {
+        *byte = 0;
+    }
+}
+
+/// Reads a null-terminated string out of a fixed size field. When there is no
+/// terminator, the whole field is used. Invalid UTF-8 is replaced.
+pub(crate) fn read_c_string(field: &[u8]) -> String {
+    String::from_utf8_lossy(c_string_bytes(field)).into_owned()
+}
+
+/// Copies a string into a fixed size, null-terminated field. The remainder of
+/// the field is zeroed. Returns false, leaving the field untouched, when the
+/// string does not fit with room for the terminator.
+pub(crate) fn write_c_string(field: &mut [u8], value: &str) -> bool {
+    if value.len() >= field.len() {
+        return false;
+    }
+
+    field[..value.len()].copy_from_slice(value.as_bytes());
+    for byte in field[value.len()..].iter_mut() {
+        *byte = 0;
+    }
+    true
+}
+
+#[cfg(feature = "serde")]
+mod serde_support {
+    use super::{Header, BOOT_ARGUMENTS_SIZE, MAGIC_SIZE, PRODUCT_NAME_SIZE, UNIQUE_ID_SIZE,
+                UniqueId, read_c_string, to_hex, write_c_string};
+    use std::convert::TryFrom;
+
+    /// The serialized representation of a header.
+    #[derive(Serialize, Deserialize)]
+    pub struct HeaderRepr {
+        magic: String,
+        kernel_size: u32,
+        kernel_load_address: u32,
+        ramdisk_size: u32,
+        ramdisk_load_address: u32,
+        second_size: u32,
+        second_load_address: u32,
+        device_tree_size: u32,
+        reserved: u32,
+        kernel_tags_address: u32,
+        page_size: u32,
+        product_name: String,
+        cmdline: String,
+        unique_id: String,
+    }
+
+    impl From<Header> for HeaderRepr {
+        fn from(header: Header) -> Self {
+            HeaderRepr {
+                magic: to_hex(&header.magic),
+                kernel_size: header.kernel_size,
+                kernel_load_address: header.kernel_load_address,
+                ramdisk_size: header.ramdisk_size,
+                ramdisk_load_address: header.ramdisk_load_address,
+                second_size: header.second_size,
+                second_load_address: header.second_load_address,
+                device_tree_size: header.device_tree_size,
+                reserved: header._reserved,
+                kernel_tags_address: header.kernel_tags_address,
+                page_size: header.page_size,
+                product_name: read_c_string(&header.product_name),
+                cmdline: header.cmdline(),
+                unique_id: to_hex(&header.unique_id),
+            }
+        }
+    }
+
+    impl TryFrom<HeaderRepr> for Header {
+        type Error = String;
+
+        fn try_from(repr: HeaderRepr) -> Result<Self, String> {
+            let mut header = Header {
+                magic: [0; MAGIC_SIZE],
+                kernel_size: repr.kernel_size,
+                kernel_load_address: repr.kernel_load_address,
+                ramdisk_size: repr.ramdisk_size,
+                ramdisk_load_address: repr.ramdisk_load_address,
+                second_size: repr.second_size,
+                second_load_address: repr.second_load_address,
+                device_tree_size: repr.device_tree_size,
+                _reserved: repr.reserved,
+                kernel_tags_address: repr.kernel_tags_address,
+                page_size: repr.page_size,
+                product_name: [0; PRODUCT_NAME_SIZE],
+                boot_arguments: [[0; BOOT_ARGUMENTS_SIZE / 16]; 16],
+                unique_id: UniqueId([0; UNIQUE_ID_SIZE]),
+            };
+
+            from_hex(&repr.magic, &mut header.magic)?;
+            from_hex(&repr.unique_id, &mut header.unique_id)?;
+            // A read header can fill a string field entirely, without a null
+            // terminator, so such strings are accepted here as well.
+            if !write_string_field(&mut header.product_name, &repr.product_name) {
+                return Err("the product name does not fit in the header".into());
+            }
+            if !write_string_field(header.boot_arguments_bytes_mut(), &repr.cmdline) {
+                return Err("the command line does not fit in the header".into());
+            }
+            Ok(header)
+        }
+    }
+
+    /// Writes a string into a fixed size field, padding it with zeroes. Unlike
+    /// `write_c_string`, the string may fill the whole field. Returns false if
+    /// the string is longer than the field.
+    fn write_string_field(field: &mut [u8], value: &str) -> bool {
+        if value.len() == field.len() {
+            field.copy_from_slice(value.as_bytes());
+            true
+        } else {
+            write_c_string(field, value)
+        }
+    }
+
+    /// Parses a hexadecimal string into a fixed size field. The string has to
+    /// describe exactly as many bytes as the field is long.
+    fn from_hex(hex: &str, field: &mut [u8]) -> Result<(), String> {
+        if !hex.is_ascii() || hex.len() != field.len() * 2 {
+            return Err(format!("expected {} hexadecimal digits", field.len() * 2));
+        }
+
+        for (byte, digits) in field.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            // The string is ASCII, so every chunk is valid UTF-8.
+            let digits = ::std::str::from_utf8(digits).unwrap();
+            *byte = u8::from_str_radix(digits, 16)
+                .map_err(|_| format!("'{}' is not a hexadecimal byte", digits))?;
         }
+        Ok(())
     }
 }
@@ -1,9 +1,32 @@
 use Header;
+use header::{DEFAULT_RESERVED, HEADER_SIZE, MAGIC, MAGIC_SIZE, MAGIC_STR, clear_c_string_padding,
+             read_c_string};
+use {Compression, Endianness, KernelFormat, SecondStageKind, SectionKind, SectionOrder,
+     UniqueId, ValidationIssue};
+use crc32::Crc32;
+#[cfg(feature = "decompress")]
+use cpio::{CpioEntry, CpioError, parse_cpio, replace_cpio_file};
+use dtb::DtbIter;
+use progress::Progress;
+use qcdt::{QcdtEntry, QcdtError, parse_qcdt};
+use reader::BootImageReader;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io::{Error as IoError, Read, Seek, Write};
 use std::path::Path;
 
 /// A structure representing a boot image in memory. Used to modify the boot
 /// image through a convenient interface.
+///
+/// Two boot images compare equal when their headers and all of their sections
+/// are equal. Use `sections_eq` to compare only the section contents.
+///
+/// The `Debug` output only shows the length and the first and last few bytes of
+/// every section.
+///
+/// As the header stores their sizes in 32 bits, sections can be at most 4 GiB
+/// large.
+#[derive(Clone, PartialEq, Eq)]
 pub struct BootImage {
     /// The header of this boot image.
     header: Header,
@@ -11,13 +34,40 @@ pub struct BootImage {
     kernel: Vec<u8>,
     /// The ramdisk.
     ramdisk: Vec<u8>,
-    /// The second ramdisk.
-    second_ramdisk: Vec<u8>,
+    /// The optional second file. This is usually a second ramdisk, but can
+    /// also be a second stage bootloader.
+    second: Vec<u8>,
     /// The device tree.
     device_tree: Vec<u8>,
+    /// Data stored after the last page of the last section, like a boot
+    /// signature or vendor data.
+    trailing_bytes: Vec<u8>,
+    /// The order the sections are stored in.
+    section_order: SectionOrder,
 }
 
 impl BootImage {
+    /// Creates a boot image from a header and all of its sections. The section
+    /// sizes in the header are computed from the sections.
+    ///
+    /// This function fails when the header would be rejected by
+    /// `insert_header`.
+    pub fn with_sections(
+        header: Header,
+        kernel: Vec<u8>,
+        ramdisk: Vec<u8>,
+        second: Vec<u8>,
+        device_tree: Vec<u8>,
+    ) -> Result<Self, BadHeaderError> {
+        let mut boot_image = BootImage::default();
+        boot_image.insert_header(header)?;
+        boot_image.insert_kernel(kernel);
+        boot_image.insert_ramdisk(ramdisk);
+        boot_image.insert_second_ramdisk(second);
+        boot_image.insert_device_tree(device_tree);
+        Ok(boot_image)
+    }
+
     /// Inserts a new header into this boot image. The sizes of the different
     /// sections (kernel, ramdisk, ...) will be updated with the ones in this
     /// boot image.
@@ -26,52 +76,330 @@ impl BootImage {
     /// when its page size is set to 0.
     ///
     /// Returns the old header on success.
-    pub fn insert_header(&mut self, mut new_header: Header) -> Result<Header, BadHeaderError> {
-        if !new_header.has_correct_magic() {
-            Err(BadHeaderError::BadMagic(new_header))
-        } else if new_header.page_size == 0 {
-            Err(BadHeaderError::NoPageSize(new_header))
-        } else {
-            ::std::mem::swap(&mut self.header, &mut new_header);
-            self.update_all_sizes();
-            Ok(new_header)
+    pub fn insert_header(&mut self, new_header: Header) -> Result<Header, BadHeaderError> {
+        self.insert_header_with_magic(new_header, &MAGIC)
+    }
+
+    /// Inserts a new header into this boot image, like `insert_header`, but
+    /// expecting the header to have the given magic instead of the 'ANDROID!'
+    /// one.
+    pub fn insert_header_with_magic(
+        &mut self,
+        new_header: Header,
+        expected_magic: &[u8; MAGIC_SIZE],
+    ) -> Result<Header, BadHeaderError> {
+        let mut new_header = check_header_with_magic(new_header, expected_magic)?;
+        ::std::mem::swap(&mut self.header, &mut new_header);
+        self.update_all_sizes();
+        Ok(new_header)
+    }
+
+    /// Copies the addressing fields from another header into the header of this
+    /// boot image: the load addresses, the kernel tags address, the page size,
+    /// the boot arguments and the product name. Unlike `insert_header`, this
+    /// keeps the rest of the header, and the section sizes stay correct.
+    ///
+    /// The page size is only copied when it is not 0.
+    pub fn merge_addressing_from(&mut self, other: &Header) {
+        self.header.kernel_load_address = other.kernel_load_address;
+        self.header.ramdisk_load_address = other.ramdisk_load_address;
+        self.header.second_load_address = other.second_load_address;
+        self.header.kernel_tags_address = other.kernel_tags_address;
+        if other.page_size != 0 {
+            self.header.page_size = other.page_size;
         }
+        self.header.boot_arguments = other.boot_arguments;
+        self.header.product_name = other.product_name;
+        self.update_all_sizes();
     }
 
     /// Inserts a kernel into this boot image, returning the old one.
+    ///
+    /// Panics when the kernel is larger than 4 GiB, as its size would not fit
+    /// in the header.
     pub fn insert_kernel(&mut self, mut new_kernel: Vec<u8>) -> Vec<u8> {
-        self.header.kernel_size = new_kernel.len() as u32;
+        self.header.kernel_size = size_field(new_kernel.len());
         ::std::mem::swap(&mut self.kernel, &mut new_kernel);
         new_kernel
     }
 
     /// Inserts a ramdisk into this boot image, returning the old one.
+    ///
+    /// Panics when the ramdisk is larger than 4 GiB, as its size would not fit
+    /// in the header.
     pub fn insert_ramdisk(&mut self, mut new_ramdisk: Vec<u8>) -> Vec<u8> {
-        self.header.ramdisk_size = new_ramdisk.len() as u32;
+        self.header.ramdisk_size = size_field(new_ramdisk.len());
         ::std::mem::swap(&mut self.ramdisk, &mut new_ramdisk);
         new_ramdisk
     }
 
     /// Inserts a second ramdisk into this boot image, returning the old one.
-    pub fn insert_second_ramdisk(&mut self, mut new_second_ramdisk: Vec<u8>) -> Vec<u8> {
-        self.header.second_size = new_second_ramdisk.len() as u32;
-        ::std::mem::swap(&mut self.second_ramdisk, &mut new_second_ramdisk);
-        new_second_ramdisk
+    ///
+    /// Panics when the second ramdisk is larger than 4 GiB, as its size would not fit
+    /// in the header.
+    pub fn insert_second_ramdisk(&mut self, mut new_second: Vec<u8>) -> Vec<u8> {
+        self.header.second_size = size_field(new_second.len());
+        ::std::mem::swap(&mut self.second, &mut new_second);
+        new_second
     }
 
     /// Inserts a device tree into this boot image, returning the old one.
+    ///
+    /// Panics when the device tree is larger than 4 GiB, as its size would not fit
+    /// in the header.
     pub fn insert_device_tree(&mut self, mut new_device_tree: Vec<u8>) -> Vec<u8> {
-        self.header.device_tree_size = new_device_tree.len() as u32;
+        self.header.device_tree_size = size_field(new_device_tree.len());
         ::std::mem::swap(&mut self.device_tree, &mut new_device_tree);
         new_device_tree
     }
 
-    /// Makes sure all the section sizes in the header are correct.
-    fn update_all_sizes(&mut self) {
-        self.header.kernel_size = self.kernel.len() as u32;
-        self.header.ramdisk_size = self.ramdisk.len() as u32;
-        self.header.second_size = self.second_ramdisk.len() as u32;
-        self.header.device_tree_size = self.device_tree.len() as u32;
+    /// Inserts the data stored after the last section of this boot image,
+    /// returning the old data.
+    pub fn insert_trailing_bytes(&mut self, mut new_trailing_bytes: Vec<u8>) -> Vec<u8> {
+        ::std::mem::swap(&mut self.trailing_bytes, &mut new_trailing_bytes);
+        new_trailing_bytes
+    }
+
+    /// Removes the kernel from this boot image, setting its size to 0.
+    pub fn clear_kernel(&mut self) {
+        self.insert_kernel(Vec::new());
+    }
+
+    /// Removes the ramdisk from this boot image, setting its size to 0.
+    pub fn clear_ramdisk(&mut self) {
+        self.insert_ramdisk(Vec::new());
+    }
+
+    /// Removes the second ramdisk from this boot image, setting its size to 0.
+    pub fn clear_second_ramdisk(&mut self) {
+        self.insert_second_ramdisk(Vec::new());
+    }
+
+    /// Removes the device tree from this boot image, setting its size to 0.
+    pub fn clear_device_tree(&mut self) {
+        self.insert_device_tree(Vec::new());
+    }
+
+    /// Replaces the kernel with a copy of the given data. The old kernel is
+    /// dropped.
+    pub fn set_kernel(&mut self, data: &[u8]) {
+        self.insert_kernel(data.to_vec());
+    }
+
+    /// Replaces the ramdisk with a copy of the given data. The old ramdisk is
+    /// dropped.
+    pub fn set_ramdisk(&mut self, data: &[u8]) {
+        self.insert_ramdisk(data.to_vec());
+    }
+
+    /// Replaces the second ramdisk with a copy of the given data. The old
+    /// second ramdisk is dropped.
+    pub fn set_second_ramdisk(&mut self, data: &[u8]) {
+        self.insert_second_ramdisk(data.to_vec());
+    }
+
+    /// Replaces the device tree with a copy of the given data. The old device
+    /// tree is dropped.
+    pub fn set_device_tree(&mut self, data: &[u8]) {
+        self.insert_device_tree(data.to_vec());
+    }
+
+    /// Changes the kernel in place through a closure. The section sizes in the
+    /// header are updated afterwards.
+    pub fn patch_kernel<F: FnOnce(&mut Vec<u8>)>(&mut self, patch: F) {
+        patch(&mut self.kernel);
+        self.update_all_sizes();
+    }
+
+    /// Changes the ramdisk in place through a closure. The section sizes in the
+    /// header are updated afterwards.
+    pub fn patch_ramdisk<F: FnOnce(&mut Vec<u8>)>(&mut self, patch: F) {
+        patch(&mut self.ramdisk);
+        self.update_all_sizes();
+    }
+
+    /// Changes the second ramdisk in place through a closure. The section sizes in the
+    /// header are updated afterwards.
+    pub fn patch_second_ramdisk<F: FnOnce(&mut Vec<u8>)>(&mut self, patch: F) {
+        patch(&mut self.second);
+        self.update_all_sizes();
+    }
+
+    /// Changes the device tree in place through a closure. The section sizes in the
+    /// header are updated afterwards.
+    pub fn patch_device_tree<F: FnOnce(&mut Vec<u8>)>(&mut self, patch: F) {
+        patch(&mut self.device_tree);
+        self.update_all_sizes();
+    }
+
+    /// Makes sure all the section sizes in the header are correct. Panics when
+    /// a section is larger than 4 GiB, as its size would not fit the header.
+    pub fn update_all_sizes(&mut self) {
+        self.header.kernel_size = size_field(self.kernel.len());
+        self.header.ramdisk_size = size_field(self.ramdisk.len());
+        self.header.second_size = size_field(self.second.len());
+        self.header.device_tree_size = size_field(self.device_tree.len());
+    }
+
+    /// Brings the boot image into a canonical form, so images that only differ
+    /// in incidental details compare equal. The section sizes are updated, the
+    /// reserved field is reset to the default `0x02000000`, and the bytes after
+    /// the terminators of the product name and command line are set to 0.
+    pub fn normalize(&mut self) {
+        self.update_all_sizes();
+        self.header.set_reserved_raw(DEFAULT_RESERVED);
+        clear_c_string_padding(&mut self.header.product_name);
+        clear_c_string_padding(self.header.boot_arguments_bytes_mut());
+    }
+
+    /// Returns the sections whose size in the header does not match their
+    /// actual size, as the section, the size in the header and the actual size.
+    /// These are the sizes `update_all_sizes` would correct.
+    pub fn size_drift(&self) -> Vec<(SectionKind, u32, usize)> {
+        vec![
+            (SectionKind::Kernel, self.header.kernel_size, self.kernel.len()),
+            (SectionKind::Ramdisk, self.header.ramdisk_size, self.ramdisk.len()),
+            (SectionKind::Second, self.header.second_size, self.second.len()),
+            (SectionKind::DeviceTree, self.header.device_tree_size, self.device_tree.len()),
+        ].into_iter()
+            .filter(|&(_, header_size, actual_size)| header_size as usize != actual_size)
+            .collect()
+    }
+
+    /// Checks this boot image for problems, returning the issues that were
+    /// found. A boot image without issues has the 'ANDROID!' magic, a valid page
+    /// size, and a header whose section sizes match the sections.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        self.validate_with_magic(&MAGIC)
+    }
+
+    /// Checks this boot image for problems like `validate`, but expecting the
+    /// given magic instead of the 'ANDROID!' one. Some OEMs use a different
+    /// magic for their images.
+    pub fn validate_with_magic(&self, magic: &[u8; MAGIC_SIZE]) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        if !self.header.has_magic(magic) {
+            issues.push(ValidationIssue::BadMagic);
+        }
+        if self.header.page_size == 0 {
+            issues.push(ValidationIssue::NoPageSize);
+        } else if !self.header.has_valid_page_size() {
+            issues.push(ValidationIssue::InvalidPageSize(self.header.page_size));
+        }
+        for (section, address) in self.header.unaligned_load_addresses() {
+            issues.push(ValidationIssue::UnalignedLoadAddress { section, address });
+        }
+        for (section, header_size, actual_size) in self.size_drift() {
+            issues.push(ValidationIssue::SizeMismatch {
+                section,
+                header_size,
+                actual_size,
+            });
+        }
+        issues
+    }
+
+    /// Returns whether this boot image can safely be written and read back, as
+    /// it has no issues according to `validate`, besides unaligned load
+    /// addresses. Every section then starts at a page boundary the header
+    /// describes.
+    pub fn is_roundtrip_safe(&self) -> bool {
+        self.is_roundtrip_safe_with_magic(&MAGIC)
+    }
+
+    /// Returns whether this boot image can safely be written and read back,
+    /// like `is_roundtrip_safe`, but expecting the given magic instead of the
+    /// 'ANDROID!' one.
+    pub fn is_roundtrip_safe_with_magic(&self, magic: &[u8; MAGIC_SIZE]) -> bool {
+        self.validate_with_magic(magic)
+            .iter()
+            .all(|issue| matches!(*issue, ValidationIssue::UnalignedLoadAddress { .. }))
+    }
+
+    /// Returns a reference to the header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns a mutable reference to the header. The section sizes in the
+    /// header are not updated automatically after changing them through this
+    /// reference, see `size_drift` and `update_all_sizes`. The page size must
+    /// not be set to 0, as the offsets cannot be computed without it.
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    /// Returns whether the sections of both boot images have the same contents.
+    /// Unlike `==`, this ignores the header, including its size fields, which
+    /// might be stale.
+    pub fn sections_eq(&self, other: &Self) -> bool {
+        self.kernel == other.kernel && self.ramdisk == other.ramdisk &&
+            self.second == other.second &&
+            self.device_tree == other.device_tree
+    }
+
+    /// Returns a digest of this boot image, intended for caching and
+    /// deduplicating boot images. The digest is the SHA-256 of the header, with
+    /// its section sizes recomputed, followed by the names of the sections in
+    /// the order they are stored in, the contents of every section in that
+    /// order, and the trailing bytes, preceded by their size. Padding between
+    /// the sections is not part of the digest.
+    pub fn fingerprint(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        let mut header = self.header.clone();
+        header.kernel_size = size_field(self.kernel.len());
+        header.ramdisk_size = size_field(self.ramdisk.len());
+        header.second_size = size_field(self.second.len());
+        header.device_tree_size = size_field(self.device_tree.len());
+
+        let mut hasher = Sha256::new();
+        // Writing into the hasher cannot fail.
+        header.write_to(&mut hasher).unwrap();
+        for &section in self.section_order.sections() {
+            hasher.update(section.name());
+            hasher.update([0]);
+        }
+        for &section in self.section_order.sections() {
+            hasher.update(self.section_data(section));
+        }
+        hasher.update((self.trailing_bytes.len() as u64).to_le_bytes());
+        hasher.update(&self.trailing_bytes);
+        hasher.finalize().into()
+    }
+
+    /// Returns the unique id `mkbootimg` computes for this boot image. This is
+    /// the SHA-1 of the kernel, the ramdisk and the second section, each
+    /// followed by its size as a little-endian 32 bit integer, followed by the
+    /// device tree and its size when there is a device tree. The remaining
+    /// bytes of the id are 0.
+    pub fn compute_id(&self) -> UniqueId {
+        use sha1::{Digest, Sha1};
+
+        let mut hasher = Sha1::new();
+        for section in &[&self.kernel, &self.ramdisk, &self.second] {
+            hasher.update(section);
+            hasher.update(size_field(section.len()).to_le_bytes());
+        }
+        if !self.device_tree.is_empty() {
+            hasher.update(&self.device_tree);
+            hasher.update(size_field(self.device_tree.len()).to_le_bytes());
+        }
+
+        let mut id = [0; 32];
+        id[..20].copy_from_slice(&hasher.finalize());
+        UniqueId(id)
+    }
+
+    /// Returns the CRC-32 of the sections of this boot image. The sections are
+    /// checksummed in the order they are stored in, without the header and
+    /// without any padding.
+    pub fn crc32(&self) -> u32 {
+        let mut crc = Crc32::new();
+        for (_, section) in self.sections() {
+            crc.update(section);
+        }
+        crc.finish()
     }
 
     /// Returns the size of a single page.
@@ -91,7 +419,57 @@ impl BootImage {
 
     /// Returns a reference to the second ramdisk.
     pub fn second_ramdisk(&self) -> &[u8] {
-        &self.second_ramdisk
+        &self.second
+    }
+
+    /// Returns the format of the kernel.
+    pub fn kernel_format(&self) -> KernelFormat {
+        KernelFormat::detect(&self.kernel)
+    }
+
+    /// Returns how the kernel is compressed.
+    pub fn kernel_compression(&self) -> Compression {
+        Compression::detect(&self.kernel)
+    }
+
+    /// Returns the decompressed kernel, see `decompress`. Use `is_arm64_image`
+    /// to check whether the result is an ARM64 `Image`.
+    #[cfg(feature = "decompress")]
+    pub fn decompress_kernel(&self) -> Result<Vec<u8>, IoError> {
+        ::compression::decompress(&self.kernel)
+    }
+
+    /// Returns the entries of the ramdisk, without extracting their contents.
+    /// The ramdisk is decompressed first, see `decompress`, and then parsed as
+    /// a newc cpio archive.
+    #[cfg(feature = "decompress")]
+    pub fn ramdisk_file_list(&self) -> Result<Vec<CpioEntry>, CpioError> {
+        parse_cpio(&::compression::decompress(&self.ramdisk)?)
+    }
+
+    /// Replaces the contents of a file in the ramdisk, keeping all the other
+    /// files. The ramdisk is decompressed, edited as a newc cpio archive, and
+    /// compressed again in the format it was in, see `compress`. The path is
+    /// matched exactly against the names returned by `ramdisk_file_list`.
+    #[cfg(feature = "decompress")]
+    pub fn ramdisk_replace_file(&mut self, path: &str, contents: &[u8]) -> Result<(), CpioError> {
+        use compression::{compress, decompress};
+
+        let compression = Compression::detect(&self.ramdisk);
+        let archive = replace_cpio_file(&decompress(&self.ramdisk)?, path, contents)?;
+        let ramdisk = compress(&archive, compression)?;
+        self.insert_ramdisk(ramdisk);
+        Ok(())
+    }
+
+    /// Returns what the second section contains, or `None` when this boot
+    /// image has no second section.
+    pub fn second_stage_kind(&self) -> Option<SecondStageKind> {
+        if self.second.is_empty() {
+            None
+        } else {
+            Some(SecondStageKind::detect(&self.second))
+        }
     }
 
     /// Returns a reference to the device tree.
@@ -99,6 +477,61 @@ impl BootImage {
         &self.device_tree
     }
 
+    /// Consumes this boot image, returning the header, the kernel, the ramdisk,
+    /// the second ramdisk and the device tree without copying them. The
+    /// trailing bytes are dropped.
+    pub fn into_parts(self) -> (Header, Vec<u8>, Vec<u8>, Vec<u8>, Vec<u8>) {
+        (self.header, self.kernel, self.ramdisk, self.second, self.device_tree)
+    }
+
+    /// Returns the data stored after the last section of this boot image. It
+    /// starts at the page boundary after the device tree, see `total_size`.
+    pub fn trailing_bytes(&self) -> &[u8] {
+        &self.trailing_bytes
+    }
+
+    /// Returns an iterator over all the sections that are present in this boot
+    /// image, in the order they are stored in. Empty sections and the header
+    /// are skipped.
+    pub fn sections(&self) -> impl Iterator<Item = (SectionKind, &[u8])> {
+        self.section_order
+            .sections()
+            .iter()
+            .map(move |&section| (section, self.section_data(section)))
+            .filter(|&(_, section)| !section.is_empty())
+    }
+
+    /// Helper function to get the contents of a section. The header has no
+    /// contents of its own here, so it is empty.
+    pub(crate) fn section_data(&self, section: SectionKind) -> &[u8] {
+        match section {
+            SectionKind::Header => &[],
+            SectionKind::Kernel => &self.kernel,
+            SectionKind::Ramdisk => &self.ramdisk,
+            SectionKind::Second => &self.second,
+            SectionKind::DeviceTree => &self.device_tree,
+        }
+    }
+
+    /// Returns a reader streaming this boot image in the layout written by
+    /// `write_to`, without writing all of it to a buffer first.
+    pub fn reader(&self) -> BootImageReader<'_> {
+        BootImageReader::new(self)
+    }
+
+    /// Parses the device tree as a Qualcomm device tree table (QCDT), returning
+    /// its entries. Use `QcdtEntry::dtb` on the device tree to get the blob of
+    /// an entry.
+    pub fn dt_entries(&self) -> Result<Vec<QcdtEntry>, QcdtError> {
+        parse_qcdt(&self.device_tree)
+    }
+
+    /// Returns an iterator over the device tree blobs concatenated in the
+    /// device tree. Unlike `dt_entries`, this does not need a QCDT table.
+    pub fn dtbs(&self) -> DtbIter<'_> {
+        DtbIter::new(&self.device_tree)
+    }
+
     /// Returns how many pages the header is big.
     pub fn header_size_in_pages(&self) -> usize {
         size_to_size_in_pages(::std::mem::size_of::<Header>(), self.page_size())
@@ -116,7 +549,7 @@ impl BootImage {
 
     /// Returns how many pages the second ramdisk is big.
     pub fn second_ramdisk_size_in_pages(&self) -> usize {
-        size_to_size_in_pages(self.second_ramdisk.len(), self.page_size())
+        size_to_size_in_pages(self.second.len(), self.page_size())
     }
 
     /// Returns how many pages the second ramdisk is big.
@@ -131,22 +564,100 @@ impl BootImage {
 
     /// Returns the offset to the kernel, in pages.
     pub fn kernel_offset_in_pages(&self) -> usize {
-        self.header_offset_in_pages() + self.header_size_in_pages()
+        self.section_offset_in_pages(SectionKind::Kernel)
     }
 
     /// Returns the offset to the ramdisk, in pages.
     pub fn ramdisk_offset_in_pages(&self) -> usize {
-        self.kernel_offset_in_pages() + self.kernel_size_in_pages()
+        self.section_offset_in_pages(SectionKind::Ramdisk)
     }
 
     /// Returns the offset to the second ramdisk, in pages.
     pub fn second_ramdisk_offset_in_pages(&self) -> usize {
-        self.ramdisk_offset_in_pages() + self.ramdisk_size_in_pages()
+        self.section_offset_in_pages(SectionKind::Second)
     }
 
     /// Returns the offset to the device tree, in pages.
     pub fn device_tree_offset_in_pages(&self) -> usize {
-        self.second_ramdisk_offset_in_pages() + self.second_ramdisk_size_in_pages()
+        self.section_offset_in_pages(SectionKind::DeviceTree)
+    }
+
+    /// Helper function to get how many pages a section is big.
+    fn section_size_in_pages(&self, section: SectionKind) -> usize {
+        match section {
+            SectionKind::Header => self.header_size_in_pages(),
+            SectionKind::Kernel => self.kernel_size_in_pages(),
+            SectionKind::Ramdisk => self.ramdisk_size_in_pages(),
+            SectionKind::Second => self.second_ramdisk_size_in_pages(),
+            SectionKind::DeviceTree => self.device_tree_size_in_pages(),
+        }
+    }
+
+    /// Helper function to get the offset to a section following the header, in
+    /// pages, using the section order of this boot image.
+    fn section_offset_in_pages(&self, section: SectionKind) -> usize {
+        self.header_offset_in_pages() + self.header_size_in_pages() + self
+            .section_order
+            .sections()
+            .iter()
+            .take_while(|&&preceding| preceding != section)
+            .map(|&preceding| self.section_size_in_pages(preceding))
+            .sum::<usize>()
+    }
+
+    /// Returns the section, the offset and the size of every section, in bytes,
+    /// in the order they are stored in, starting with the header. Unlike
+    /// `sections`, empty sections are included with a size of 0.
+    pub fn sections_summary(&self) -> [(SectionKind, usize, usize); 5] {
+        let mut summary = [(SectionKind::Header, self.header_offset(), HEADER_SIZE); 5];
+        for (entry, &section) in summary[1..].iter_mut().zip(self.section_order.sections()) {
+            let size = match section {
+                SectionKind::Header => HEADER_SIZE,
+                SectionKind::Kernel => self.kernel.len(),
+                SectionKind::Ramdisk => self.ramdisk.len(),
+                SectionKind::Second => self.second.len(),
+                SectionKind::DeviceTree => self.device_tree.len(),
+            };
+            let offset = self.section_offset_in_pages(section) * self.page_size();
+            *entry = (section, offset, size);
+        }
+        summary
+    }
+
+    /// Returns the section, the size of its contents and its size including
+    /// the padding up to the next page, in bytes, of every section. The sections
+    /// are in the order they are stored in, starting with the header, so the
+    /// difference between both sizes shows how much space the padding takes.
+    pub fn page_breakdown(&self) -> Vec<(SectionKind, usize, usize)> {
+        self.sections_summary()
+            .iter()
+            .map(|&(section, _, size)| {
+                (section, size, self.section_size_in_pages(section) * self.page_size())
+            })
+            .collect()
+    }
+
+    /// Returns the size of the whole boot image, in bytes, including the
+    /// padding after every section. The trailing bytes are not included.
+    pub fn total_size(&self) -> usize {
+        let size_in_pages = self.header_size_in_pages() + self
+            .section_order
+            .sections()
+            .iter()
+            .map(|&section| self.section_size_in_pages(section))
+            .sum::<usize>();
+        size_in_pages * self.page_size()
+    }
+
+    /// Returns the order the sections of this boot image are stored in.
+    pub fn section_order(&self) -> SectionOrder {
+        self.section_order
+    }
+
+    /// Sets the order the sections of this boot image are stored in. This
+    /// changes the offsets of the sections, and the layout that is written.
+    pub fn set_section_order(&mut self, order: SectionOrder) {
+        self.section_order = order;
     }
 
     /// Returns the offset to the header, in bytes.
@@ -179,58 +690,394 @@ impl BootImage {
     /// that is between the different sections.
     ///
     /// As some boot images have their page size set to 0, an override page
-    /// size can be supplied. If the header size is set to 0, and no valid
-    /// override is supplied, this function will return an error.
+    /// size can be supplied. If the header size is set to 0, and no override is
+    /// supplied, the page size is inferred using `infer_page_size`. If that
+    /// fails too, this function will return an error. A page size that is not
+    /// 0, but not one used by Android devices either, is rejected with
+    /// `ReadBootImageError::InvalidPageSize` unless an override is supplied.
     pub fn read_from<R: Read + Seek>(
         source: &mut R,
         override_page_size: Option<u32>,
+    ) -> Result<Self, ReadBootImageError> {
+        BootImage::read_from_with_magic(source, override_page_size, &MAGIC)
+    }
+
+    /// Reads the boot image from a readable source, like `read_from`, but
+    /// expecting the header to have the given magic instead of the 'ANDROID!'
+    /// one. This allows reading images of OEMs that rebranded the magic.
+    pub fn read_from_with_magic<R: Read + Seek>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+        expected_magic: &[u8; MAGIC_SIZE],
+    ) -> Result<Self, ReadBootImageError> {
+        let options = ReadOptions {
+            expected_magic: *expected_magic,
+            ..ReadOptions::default()
+        };
+        BootImage::read_from_opts(source, override_page_size, &options)
+    }
+
+    /// Reads the boot image from a readable source, like `read_from`, with
+    /// control over how the sections are read. See `ReadOptions`.
+    pub fn read_from_opts<R: Read + Seek>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+        options: &ReadOptions,
     ) -> Result<Self, ReadBootImageError> {
         use std::io::SeekFrom;
 
         let mut boot_image = BootImage::default();
-        let mut header = Header::read_from(source)?;
+        let mut header = Header::read_from_endian(source, options.endianness)?;
+        // Check the magic before anything else, so a file that is not a boot
+        // image fails right away instead of after inferring its page size.
+        let expected_magic = if options.force {
+            header.magic
+        } else {
+            options.expected_magic
+        };
+        if !header.has_magic(&expected_magic) {
+            return Err(BadHeaderError::BadMagic(header).into());
+        }
+        if options.strict_strings {
+            if !header.product_name.contains(&0) {
+                return Err(BadHeaderError::ProductNameTooLong(header).into());
+            }
+            if !header.boot_arguments_bytes().contains(&0) {
+                return Err(BadHeaderError::CmdlineTooLong(header).into());
+            }
+        }
+        let invalid_page_size =
+            override_page_size.is_none() && header.page_size != 0 && !header.has_valid_page_size();
+        if invalid_page_size && !options.force {
+            return Err(ReadBootImageError::InvalidPageSize(header.page_size));
+        }
         header.page_size = override_page_size.unwrap_or(header.page_size);
+        if header.page_size == 0 || invalid_page_size {
+            header.page_size =
+                BootImage::infer_page_size_endian(source, options.endianness).unwrap_or(0);
+        }
+        if options.samsung_dt_quirk && header.page_size != 0 {
+            if let Some(device_tree_size) = header.dt_size_from_reserved() {
+                // Only trust the reserved field when the device tree it
+                // describes fits in the source.
+                let mut quirk_header = header.clone();
+                quirk_header.device_tree_size = device_tree_size;
+                let device_tree_end = section_offset(
+                    &quirk_header,
+                    &options.section_order,
+                    SectionKind::DeviceTree,
+                ) as u64 + u64::from(device_tree_size);
+                if device_tree_end <= source_len(source)? {
+                    header = quirk_header;
+                }
+            }
+        }
 
         // We need to clone the header here, inserting the header will remove all
         // knowledge about the sizes of the different sections, and keeping the header
         // around for later will also delay the validation checks. Delaying the
         // validation checks means we might try to read in section data that might not
         // exist, causing I/O errors that hide the real validation errors.
-        let _ = boot_image.insert_header(header.clone())?;
-
-        // Read all the different sections into memory.
-        {
-            let mut kernel = vec![0; header.kernel_size as usize];
-            source
-                .seek(SeekFrom::Start(boot_image.kernel_offset() as u64))?;
-            source.read_exact(&mut kernel)?;
-            boot_image.insert_kernel(kernel);
-        }
-        {
-            let mut ramdisk = vec![0; header.ramdisk_size as usize];
-            source
-                .seek(SeekFrom::Start(boot_image.ramdisk_offset() as u64))?;
-            source.read_exact(&mut ramdisk)?;
-            boot_image.insert_ramdisk(ramdisk);
-        }
-        {
-            let mut second_ramdisk = vec![0; header.second_size as usize];
-            source
-                .seek(SeekFrom::Start(boot_image.second_ramdisk_offset() as u64))?;
-            source.read_exact(&mut second_ramdisk)?;
-            boot_image.insert_second_ramdisk(second_ramdisk);
-        }
-        {
-            let mut device_tree = vec![0; header.device_tree_size as usize];
-            source
-                .seek(SeekFrom::Start(boot_image.device_tree_offset() as u64))?;
-            source.read_exact(&mut device_tree)?;
-            boot_image.insert_device_tree(device_tree);
+        let _ = boot_image.insert_header_with_magic(header.clone(), &expected_magic)?;
+        if let Some(ref progress) = options.progress {
+            progress.report(SectionKind::Header, HEADER_SIZE);
+        }
+
+        for &section in &[
+            SectionKind::Kernel,
+            SectionKind::Ramdisk,
+            SectionKind::Second,
+            SectionKind::DeviceTree,
+        ] {
+            let size = section_size(&header, section);
+            if size > options.max_section_bytes {
+                return Err(ReadBootImageError::SectionTooLarge(
+                    section,
+                    size,
+                    options.max_section_bytes,
+                ));
+            }
+        }
+
+        // Read all the different sections into memory, in the order they are
+        // stored in.
+        boot_image.section_order = options.section_order;
+        for &section in options.section_order.sections() {
+            let offset = section_offset(&header, &options.section_order, section);
+            source.seek(SeekFrom::Start(offset as u64))?;
+            let expected = section_size(&header, section);
+            let data = read_section(source, expected, options.prealloc)?;
+            if data.len() < expected {
+                return Err(ReadBootImageError::UnexpectedEof {
+                    section,
+                    expected,
+                    got: data.len(),
+                });
+            }
+            if let Some(ref progress) = options.progress {
+                progress.report(section, data.len());
+            }
+            match section {
+                SectionKind::Header => {}
+                SectionKind::Kernel => {
+                    boot_image.insert_kernel(data);
+                }
+                SectionKind::Ramdisk => {
+                    boot_image.insert_ramdisk(data);
+                }
+                SectionKind::Second => {
+                    boot_image.insert_second_ramdisk(data);
+                }
+                SectionKind::DeviceTree => {
+                    boot_image.insert_device_tree(data);
+                }
+            }
+        }
+        if options.read_trailing_bytes {
+            source.seek(SeekFrom::Start(boot_image.total_size() as u64))?;
+            let mut trailing_bytes = Vec::new();
+            source.read_to_end(&mut trailing_bytes)?;
+            boot_image.insert_trailing_bytes(trailing_bytes);
         }
 
         Ok(boot_image)
     }
 
+    /// Infers the page size of a boot image from the length of the source, for
+    /// boot images that have their page size set to 0. Returns the first of the
+    /// common page sizes for which the sections, as described by the header,
+    /// exactly fill the source. The position of the source is left unchanged.
+    pub fn infer_page_size<R: Read + Seek>(source: &mut R) -> Option<u32> {
+        BootImage::infer_page_size_endian(source, Endianness::Little)
+    }
+
+    /// Infers the page size of a boot image like `infer_page_size`, reading
+    /// the header fields in the given byte order.
+    pub fn infer_page_size_endian<R: Read + Seek>(
+        source: &mut R,
+        endianness: Endianness,
+    ) -> Option<u32> {
+        use std::io::SeekFrom;
+
+        let length = source_len(source).ok()? as usize;
+        let position = source.stream_position().ok()?;
+        let header = source
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| Header::read_from_endian(source, endianness));
+        source.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = header.ok()?;
+
+        [2048, 4096, 8192, 16384].iter().cloned().find(|&page_size| {
+            header.page_size = page_size;
+            let device_tree_end = section_offset(&header, &SectionOrder::STANDARD,
+                                                 SectionKind::DeviceTree) +
+                section_size(&header, SectionKind::DeviceTree);
+            let total_size =
+                size_to_size_in_pages(device_tree_end, page_size as usize) * page_size as usize;
+            total_size == length
+        })
+    }
+
+    /// Reads as much of a boot image as possible from a readable and seekable
+    /// source, for recovering data from damaged boot images. Instead of failing,
+    /// the problems that were encountered are returned along with the boot
+    /// image.
+    ///
+    /// Sections that are cut off by the end of the source are truncated to the
+    /// bytes that are available, and sections following them are left empty.
+    /// A header with the wrong magic is used anyway. When the header cannot be
+    /// read, or it has no valid page size and no override page size is
+    /// supplied, an empty boot image is returned.
+    pub fn read_best_effort<R: Read + Seek>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+    ) -> (Self, Vec<ValidationIssue>) {
+        BootImage::read_best_effort_opts(source, override_page_size, &ReadOptions::default())
+    }
+
+    /// Reads as much of a boot image as possible, like `read_best_effort`, with
+    /// control over the layout of the boot image. Only the expected magic, the
+    /// section order and the endianness of the options are used.
+    pub fn read_best_effort_opts<R: Read + Seek>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+        options: &ReadOptions,
+    ) -> (Self, Vec<ValidationIssue>) {
+        use std::io::SeekFrom;
+
+        let mut boot_image = BootImage::default();
+        let mut issues = Vec::new();
+
+        let mut header = match Header::read_from_endian(source, options.endianness) {
+            Ok(header) => header,
+            Err(_) => return (boot_image, vec![ValidationIssue::UnreadableHeader]),
+        };
+        header.page_size = override_page_size.unwrap_or(header.page_size);
+        if !header.has_magic(&options.expected_magic) {
+            issues.push(ValidationIssue::BadMagic);
+        }
+        if header.page_size == 0 {
+            issues.push(ValidationIssue::NoPageSize);
+            return (boot_image, issues);
+        }
+        if override_page_size.is_none() && !header.has_valid_page_size() {
+            issues.push(ValidationIssue::InvalidPageSize(header.page_size));
+            return (boot_image, issues);
+        }
+
+        for &section in options.section_order.sections() {
+            let expected = section_size(&header, section);
+            let mut data = Vec::new();
+            let result = source
+                .seek(SeekFrom::Start(
+                    section_offset(&header, &options.section_order, section) as u64,
+                ))
+                .and_then(|_| source.by_ref().take(expected as u64).read_to_end(&mut data));
+            if result.is_err() || data.len() < expected {
+                issues.push(ValidationIssue::TruncatedSection {
+                    section,
+                    expected,
+                    got: data.len(),
+                });
+            }
+            match section {
+                SectionKind::Header => {}
+                SectionKind::Kernel => boot_image.kernel = data,
+                SectionKind::Ramdisk => boot_image.ramdisk = data,
+                SectionKind::Second => boot_image.second = data,
+                SectionKind::DeviceTree => boot_image.device_tree = data,
+            }
+        }
+
+        // The header is set directly, as it might not have passed the checks of
+        // `insert_header`.
+        boot_image.header = header;
+        boot_image.section_order = options.section_order;
+        boot_image.update_all_sizes();
+        (boot_image, issues)
+    }
+
+    /// Reads only the header of a boot image from a readable source, without
+    /// reading any of the sections. Exactly the bytes of the header are read
+    /// from the source.
+    ///
+    /// As some boot images have their page size set to 0, an override page
+    /// size can be supplied. If the header size is set to 0, and no valid
+    /// override is supplied, this function will return an error, as the page
+    /// size cannot be inferred without reading the rest of the boot image. An
+    /// invalid page size is rejected like `read_from` does.
+    pub fn read_header_only<R: Read>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+    ) -> Result<Header, ReadBootImageError> {
+        let mut header = Header::read_from(source)?;
+        check_page_size(&header, override_page_size)?;
+        header.page_size = override_page_size.unwrap_or(header.page_size);
+        Ok(check_header(header)?)
+    }
+
+    /// Reads the header of a boot image that might be truncated, like
+    /// `read_header_only`, but accepting a source that ends before the header
+    /// does. The missing bytes are taken to be 0, so the addresses and sizes
+    /// can be recovered even when the unique id at the end was cut off. Returns
+    /// the header and the amount of header bytes that were available.
+    ///
+    /// The header is not checked, as this is meant for recovering damaged
+    /// images. Use `Header::has_correct_magic` to check its magic.
+    pub fn read_header_lenient<R: Read>(source: &mut R) -> Result<(Header, usize), IoError> {
+        let available = read_section(source, HEADER_SIZE, true)?;
+        let mut buffer = [0; HEADER_SIZE];
+        buffer[..available.len()].copy_from_slice(&available);
+        Ok((Header::parse(&buffer), available.len()))
+    }
+
+    /// Reads part of a single section from a readable and seekable source,
+    /// without reading the other sections or the rest of the section. Only the
+    /// header is parsed, after which `length` bytes are read, starting `offset`
+    /// bytes into the section.
+    ///
+    /// As some boot images have their page size set to 0, an override page
+    /// size can be supplied. If the header size is set to 0, and no valid
+    /// override is supplied, this function will return an error. An invalid
+    /// page size is rejected like `read_from` does. It also fails when the
+    /// range does not lie within the section.
+    pub fn read_section_range<R: Read + Seek>(
+        source: &mut R,
+        section: SectionKind,
+        offset: usize,
+        length: usize,
+        override_page_size: Option<u32>,
+    ) -> Result<Vec<u8>, ReadBootImageError> {
+        use std::io::{ErrorKind as IoErrorKind, SeekFrom};
+
+        let mut header = Header::read_from(source)?;
+        check_page_size(&header, override_page_size)?;
+        header.page_size = override_page_size.unwrap_or(header.page_size);
+        let header = check_header(header)?;
+
+        match offset.checked_add(length) {
+            Some(end) if end <= section_size(&header, section) => {}
+            _ => {
+                return Err(IoError::new(
+                    IoErrorKind::InvalidInput,
+                    "the range does not lie within the section",
+                ).into())
+            }
+        }
+
+        let mut output = vec![0; length];
+        let section_offset = section_offset(&header, &SectionOrder::STANDARD, section);
+        source.seek(SeekFrom::Start((section_offset + offset) as u64))?;
+        source.read_exact(&mut output)?;
+        Ok(output)
+    }
+
+    /// Reads all the boot images from a source containing several of them
+    /// back-to-back, like some OTA payloads. Every boot image is expected to
+    /// start right after the last page of the previous one, or after zeroed
+    /// pages following it. Reading stops at the end of the source, or at data
+    /// that is neither padding nor a boot image.
+    ///
+    /// The page sizes of the boot images are taken from their headers. A page
+    /// size of 0 can only be inferred for the last boot image, as inferring it
+    /// relies on the boot image ending at the end of the source, see
+    /// `infer_page_size`. Fails when the first boot image cannot be read, or
+    /// when any of the boot images is damaged.
+    pub fn read_all<R: Read + Seek>(source: &mut R) -> Result<Vec<Self>, ReadBootImageError> {
+        use std::io::SeekFrom;
+
+        let mut boot_images = Vec::new();
+        let mut offset = 0;
+        loop {
+            if !boot_images.is_empty() {
+                let mut magic = Vec::new();
+                source.seek(SeekFrom::Start(offset))?;
+                source.by_ref().take(MAGIC_STR.len() as u64).read_to_end(&mut magic)?;
+
+                if magic.is_empty() {
+                    break;
+                } else if magic.iter().all(|&byte| byte == 0) {
+                    // Skip a page of padding, using the page size of the previous
+                    // boot image.
+                    offset += boot_images.last().map_or(0, BootImage::page_size) as u64;
+                    continue;
+                } else if magic != MAGIC_STR.as_bytes() {
+                    break;
+                }
+            }
+
+            let options = ReadOptions {
+                read_trailing_bytes: false,
+                ..ReadOptions::default()
+            };
+            let boot_image =
+                BootImage::read_from_opts(&mut SubSource::new(source, offset)?, None, &options)?;
+            offset += boot_image.total_size() as u64;
+            boot_images.push(boot_image);
+        }
+        Ok(boot_images)
+    }
+
     /// Reads the boot image from a file.
     ///
     /// As some boot images have their page size set to 0, an override page
@@ -246,18 +1093,190 @@ impl BootImage {
         BootImage::read_from(&mut file_handle, override_page_size)
     }
 
+    /// Reads the boot image from a source that might be compressed as a whole,
+    /// like a downloaded `boot.img.gz`. A gzip compressed source is
+    /// decompressed into memory first, any other source is read as-is with
+    /// `read_from`.
+    #[cfg(feature = "decompress")]
+    pub fn read_from_compressed<R: Read + Seek>(
+        source: &mut R,
+        override_page_size: Option<u32>,
+    ) -> Result<Self, ReadBootImageError> {
+        use flate2::read::GzDecoder;
+        use std::io::SeekFrom;
+
+        let position = source.stream_position()?;
+        let mut magic = Vec::new();
+        source.by_ref().take(2).read_to_end(&mut magic)?;
+        source.seek(SeekFrom::Start(position))?;
+
+        if Compression::detect(&magic) == Compression::Gzip {
+            let mut data = Vec::new();
+            GzDecoder::new(source).read_to_end(&mut data)?;
+            BootImage::from_bytes(&data, override_page_size)
+        } else {
+            BootImage::read_from(source, override_page_size)
+        }
+    }
+
+    /// Reads the boot image from an in-memory buffer.
+    ///
+    /// As some boot images have their page size set to 0, an override page
+    /// size can be supplied. If the header size is set to 0, and no valid
+    /// override is supplied, this function will return an error.
+    pub fn from_bytes(
+        data: &[u8],
+        override_page_size: Option<u32>,
+    ) -> Result<Self, ReadBootImageError> {
+        use std::io::Cursor;
+
+        BootImage::read_from(&mut Cursor::new(data), override_page_size)
+    }
+
+    /// Writes this boot image to a new buffer, in the layout written by
+    /// `write_to`. The result can be read back with `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        // Writing into a vector cannot fail.
+        self.write_to(&mut output).unwrap();
+        output
+    }
+
     /// Writes this boot image to a `Write` target. Returns the amount of bytes
-    /// written.
+    /// written, including the padding.
+    ///
+    /// The header and every section are padded with zeroes up to the next page
+    /// boundary, so every section starts at the offset `read_from` expects.
+    /// Empty sections take up no pages. The trailing bytes follow the last
+    /// page.
     pub fn write_to<W: Write>(&self, target: &mut W) -> Result<usize, IoError> {
+        self.write_to_opts(target, &WriteOptions::default())
+    }
+
+    /// Writes this boot image to a `Write` target in the layout of `write_to`,
+    /// with control over how it is written. See `WriteOptions`. Returns the
+    /// amount of bytes written, including the padding.
+    pub fn write_to_opts<W: Write>(
+        &self,
+        target: &mut W,
+        options: &WriteOptions,
+    ) -> Result<usize, IoError> {
+        let (page_size, pad_byte) = (self.page_size(), options.pad_byte);
         let mut bytes_written = 0;
-        bytes_written += self.write_header_to(target)?;
-        bytes_written += self.write_kernel_to(target)?;
-        bytes_written += self.write_ramdisk_to(target)?;
-        bytes_written += self.write_second_ramdisk_to(target)?;
-        bytes_written += self.write_device_tree_to(target)?;
+        let header_size = if options.update_id {
+            let mut header = self.header.clone();
+            header.unique_id = self.compute_id();
+            header.write_to_endian(target, options.endianness)?
+        } else {
+            self.header.write_to_endian(target, options.endianness)?
+        };
+        let header_size = write_padded(header_size, page_size, pad_byte, target)?;
+        if let Some(ref progress) = options.progress {
+            progress.report(SectionKind::Header, header_size);
+        }
+        bytes_written += header_size;
+        for &section in self.section_order.sections() {
+            let size = self.write_sections_concatenated(&[section], target)?;
+            let size = write_padded(size, page_size, pad_byte, target)?;
+            if let Some(ref progress) = options.progress {
+                progress.report(section, size);
+            }
+            bytes_written += size;
+        }
+        target.write_all(&self.trailing_bytes)?;
+        bytes_written += self.trailing_bytes.len();
+        Ok(bytes_written)
+    }
+
+    /// Writes this boot image like `write_to`, then reads it back from the
+    /// target and checks that it equals this boot image. Returns the amount of
+    /// bytes written. Fails with `WriteVerifiedError::RoundtripMismatch` when
+    /// the boot image that was read back differs, for example because the
+    /// section sizes in the header are stale.
+    pub fn write_verified<W: Write + Read + Seek>(
+        &self,
+        target: &mut W,
+    ) -> Result<usize, WriteVerifiedError> {
+        use std::io::SeekFrom;
+
+        let start = target.stream_position()?;
+        let bytes_written = self.write_to(target)?;
+
+        let mut source = SubSource::new(target, start)?;
+        let options = ReadOptions {
+            expected_magic: self.header.magic,
+            read_trailing_bytes: false,
+            section_order: self.section_order,
+            ..ReadOptions::default()
+        };
+        let mut read_back =
+            BootImage::read_from_opts(&mut source, Some(self.header.page_size), &options)?;
+        source.seek(SeekFrom::Start(self.total_size() as u64))?;
+        read_back.insert_trailing_bytes(read_section(
+            &mut source,
+            self.trailing_bytes.len(),
+            true,
+        )?);
+        source.seek(SeekFrom::Start(bytes_written as u64))?;
+
+        if read_back != *self {
+            return Err(WriteVerifiedError::RoundtripMismatch);
+        }
         Ok(bytes_written)
     }
 
+    /// Writes the chosen sections back-to-back to a `Write` target, without
+    /// any padding. Returns the amount of bytes written.
+    pub fn write_sections_concatenated<W: Write>(
+        &self,
+        sections: &[SectionKind],
+        target: &mut W,
+    ) -> Result<usize, IoError> {
+        let mut bytes_written = 0;
+        for &section in sections {
+            bytes_written += match section {
+                SectionKind::Header => self.write_header_to(target)?,
+                SectionKind::Kernel => self.write_kernel_to(target)?,
+                SectionKind::Ramdisk => self.write_ramdisk_to(target)?,
+                SectionKind::Second => self.write_second_ramdisk_to(target)?,
+                SectionKind::DeviceTree => self.write_device_tree_to(target)?,
+            };
+        }
+        Ok(bytes_written)
+    }
+
+    /// Writes the section with the given name to a `Write` target. Returns the
+    /// amount of bytes written. The names are those of `SectionKind::name`:
+    /// `"header"`, `"kernel"`, `"ramdisk"`, `"second"` and `"dt"`; other names
+    /// cause an error of the `InvalidInput` kind.
+    pub fn write_section_to<W: Write>(
+        &self,
+        section: &str,
+        target: &mut W,
+    ) -> Result<usize, IoError> {
+        use std::io::ErrorKind as IoErrorKind;
+
+        let section = section
+            .parse::<SectionKind>()
+            .map_err(|error| IoError::new(IoErrorKind::InvalidInput, error.to_string()))?;
+        self.write_sections_concatenated(&[section], target)
+    }
+
+    /// Writes a section to a `Write` target, like `write_sections_concatenated`,
+    /// but failing with `WriteSectionError::SectionAbsent` when the section is
+    /// empty. Nothing is written to the target in that case. Returns the amount
+    /// of bytes written.
+    pub fn write_section_checked<W: Write>(
+        &self,
+        section: SectionKind,
+        target: &mut W,
+    ) -> Result<usize, WriteSectionError> {
+        if section != SectionKind::Header && self.section_data(section).is_empty() {
+            return Err(WriteSectionError::SectionAbsent(section));
+        }
+        Ok(self.write_sections_concatenated(&[section], target)?)
+    }
+
     /// Writes the header to a `Write` target. Returns the amount of bytes
     /// written.
     pub fn write_header_to<W: Write>(&self, target: &mut W) -> Result<usize, IoError> {
@@ -281,8 +1300,8 @@ impl BootImage {
     /// Writes the second ramdisk to a `Write` target. Returns the amount of
     /// bytes written.
     pub fn write_second_ramdisk_to<W: Write>(&self, target: &mut W) -> Result<usize, IoError> {
-        target.write_all(&self.second_ramdisk)?;
-        Ok(self.second_ramdisk.len())
+        target.write_all(&self.second)?;
+        Ok(self.second.len())
     }
 
     /// Writes the device tree to a `Write` target. Returns the amount of bytes
@@ -293,12 +1312,311 @@ impl BootImage {
     }
 }
 
+/// Positions a seekable source at the start of a section of the boot image
+/// described by the header, assuming the standard section order. Combined with
+/// `Read::take` and the section size from the header, this allows streaming a
+/// single section without reading the rest of the boot image.
+///
+/// Fails with the `InvalidInput` kind when the page size of the header is 0.
+pub fn seek_to_section<R: Seek>(
+    source: &mut R,
+    header: &Header,
+    section: SectionKind,
+) -> Result<(), IoError> {
+    use std::io::{ErrorKind as IoErrorKind, SeekFrom};
+
+    if header.page_size == 0 {
+        return Err(IoError::new(
+            IoErrorKind::InvalidInput,
+            "cannot locate a section when the page size is 0",
+        ));
+    }
+
+    let offset = section_offset(header, &SectionOrder::STANDARD, section);
+    source.seek(SeekFrom::Start(offset as u64))?;
+    Ok(())
+}
+
+/// Helper function to read a section of the given size. The section is shorter
+/// when the source ends before it does. Without preallocation, the buffer grows
+/// while reading, so a corrupt size fails on the end of the source before
+/// allocating all of it.
+fn read_section<R: Read>(source: &mut R, size: usize, prealloc: bool) -> Result<Vec<u8>, IoError> {
+    let mut section = if prealloc { Vec::with_capacity(size) } else { Vec::new() };
+    source.take(size as u64).read_to_end(&mut section)?;
+    Ok(section)
+}
+
+/// Helper function to get the length of a seekable source, in bytes. The
+/// position of the source is left unchanged.
+pub(crate) fn source_len<R: Seek>(source: &mut R) -> Result<u64, IoError> {
+    use std::io::SeekFrom;
+
+    let position = source.stream_position()?;
+    let length = source.seek(SeekFrom::End(0))?;
+    source.seek(SeekFrom::Start(position))?;
+    Ok(length)
+}
+
+/// Helper function to make sure a header can be used for a boot image. The
+/// header must have the valid magic, and its page size must not be 0.
+pub(crate) fn check_header(header: Header) -> Result<Header, BadHeaderError> {
+    check_header_with_magic(header, &MAGIC)
+}
+
+/// Helper function like `check_header`, but expecting the given magic.
+fn check_header_with_magic(
+    header: Header,
+    expected_magic: &[u8; MAGIC_SIZE],
+) -> Result<Header, BadHeaderError> {
+    if !header.has_magic(expected_magic) {
+        Err(BadHeaderError::BadMagic(header))
+    } else if header.page_size == 0 {
+        Err(BadHeaderError::NoPageSize(header))
+    } else {
+        Ok(header)
+    }
+}
+
+/// Helper function to reject a page size that is not 0, but not used by Android
+/// devices either, unless an override page size is supplied.
+pub(crate) fn check_page_size(
+    header: &Header,
+    override_page_size: Option<u32>,
+) -> Result<(), ReadBootImageError> {
+    if override_page_size.is_none() && header.page_size != 0 && !header.has_valid_page_size() {
+        Err(ReadBootImageError::InvalidPageSize(header.page_size))
+    } else {
+        Ok(())
+    }
+}
+
+/// Helper function to get the size of a section, in bytes, as described by a
+/// header.
+pub(crate) fn section_size(header: &Header, section: SectionKind) -> usize {
+    match section {
+        SectionKind::Header => ::std::mem::size_of::<Header>(),
+        SectionKind::Kernel => header.kernel_size as usize,
+        SectionKind::Ramdisk => header.ramdisk_size as usize,
+        SectionKind::Second => header.second_size as usize,
+        SectionKind::DeviceTree => header.device_tree_size as usize,
+    }
+}
+
+/// Helper function to get the offset to a section, in bytes, as described by a
+/// header and the order of the sections. The header's page size must not be 0.
+pub(crate) fn section_offset(header: &Header, order: &SectionOrder, section: SectionKind) -> usize {
+    let page_size = header.page_size as usize;
+
+    ::std::iter::once(&SectionKind::Header)
+        .chain(order.sections().iter())
+        .take_while(|&&preceding| preceding != section)
+        .map(|&preceding| size_to_size_in_pages(section_size(header, preceding), page_size))
+        .sum::<usize>() * page_size
+}
+
+/// Helper function to convert the size of a section to the value of its size
+/// field in the header. Panics when the size does not fit, as a truncated size
+/// would make the boot image unreadable.
+fn size_field(size: usize) -> u32 {
+    assert!(
+        size <= u32::MAX as usize,
+        "sections larger than 4 GiB are not supported"
+    );
+    size as u32
+}
+
 /// Helper function to calculate how big something would be in pages, given
 /// the size and the page size.
-fn size_to_size_in_pages(size: usize, page_size: usize) -> usize {
+// `usize::div_ceil` needs a newer Rust than this crate supports.
+#[allow(clippy::manual_div_ceil)]
+pub(crate) fn size_to_size_in_pages(size: usize, page_size: usize) -> usize {
     (size + page_size - 1) / page_size
 }
 
+/// Helper function to pad a section that was just written up to the next page
+/// boundary with the given byte. Takes the size of the section, and returns the
+/// size of the section including the padding.
+fn write_padded<W: Write>(
+    size: usize,
+    page_size: usize,
+    pad_byte: u8,
+    target: &mut W,
+) -> Result<usize, IoError> {
+    let padded_size = size_to_size_in_pages(size, page_size) * page_size;
+    target.write_all(&vec![pad_byte; padded_size - size])?;
+    Ok(padded_size)
+}
+
+/// A view of a seekable source that starts at an offset into it, so boot images
+/// can be read from the middle of a source.
+struct SubSource<'a, R: 'a> {
+    /// The source this is a view of.
+    source: &'a mut R,
+    /// The offset into the source where this view starts.
+    start: u64,
+}
+
+impl<'a, R: Seek> SubSource<'a, R> {
+    /// Creates a view starting at an offset into a source, positioned at its
+    /// start.
+    fn new(source: &'a mut R, start: u64) -> Result<Self, IoError> {
+        use std::io::SeekFrom;
+
+        source.seek(SeekFrom::Start(start))?;
+        Ok(SubSource { source, start })
+    }
+}
+
+impl<'a, R: Read> Read for SubSource<'a, R> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, IoError> {
+        self.source.read(buffer)
+    }
+}
+
+impl<'a, R: Seek> Seek for SubSource<'a, R> {
+    fn seek(&mut self, position: ::std::io::SeekFrom) -> Result<u64, IoError> {
+        use std::io::SeekFrom;
+
+        let position = match position {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            other => other,
+        };
+        Ok(self.source.seek(position)?.saturating_sub(self.start))
+    }
+}
+
+impl fmt::Debug for BootImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BootImage")
+            .field("header", &HeaderSummary(&self.header))
+            .field("kernel", &SectionSummary(&self.kernel))
+            .field("ramdisk", &SectionSummary(&self.ramdisk))
+            .field("second", &SectionSummary(&self.second))
+            .field("device_tree", &SectionSummary(&self.device_tree))
+            .field("trailing_bytes", &SectionSummary(&self.trailing_bytes))
+            .field("section_order", &self.section_order)
+            .finish()
+    }
+}
+
+/// Helper to debug format a header without dumping its byte arrays.
+pub(crate) struct HeaderSummary<'a>(pub &'a Header);
+
+impl<'a> fmt::Debug for HeaderSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Header")
+            .field("has_correct_magic", &self.0.has_correct_magic())
+            .field("page_size", &self.0.page_size)
+            .field("product_name", &read_c_string(&self.0.product_name))
+            .field("cmdline", &self.0.cmdline())
+            .finish()
+    }
+}
+
+/// Helper to debug format a section as its length and its first and last few
+/// bytes, instead of dumping all of its contents.
+pub(crate) struct SectionSummary<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Debug for SectionSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const SHOWN_BYTES: usize = 4;
+
+        write!(f, "{} bytes", self.0.len())?;
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        if self.0.len() <= SHOWN_BYTES * 2 {
+            write!(f, " {:02x?}", self.0)
+        } else {
+            let (head, tail) = (&self.0[..SHOWN_BYTES], &self.0[self.0.len() - SHOWN_BYTES..]);
+            write!(f, " {:02x?} .. {:02x?}", head, tail)
+        }
+    }
+}
+
+impl Hash for BootImage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint().hash(state);
+    }
+}
+
+/// Options controlling how `BootImage::read_from_opts` reads a boot image.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// The magic the header is expected to have. Defaults to 'ANDROID!'.
+    pub expected_magic: [u8; MAGIC_SIZE],
+    /// The largest size a section may have, in bytes. Reading fails with
+    /// `ReadBootImageError::SectionTooLarge` when a section is larger, before
+    /// anything is allocated for it. Defaults to no limit.
+    pub max_section_bytes: usize,
+    /// Whether to allocate the memory for a section before reading it, using
+    /// the size in the header. Otherwise the memory grows while reading.
+    /// Defaults to true.
+    pub prealloc: bool,
+    /// Whether to keep everything after the last section, up to the end of
+    /// the source, as the trailing bytes of the boot image. Defaults to true.
+    pub read_trailing_bytes: bool,
+    /// The order the sections are stored in. Defaults to the standard order.
+    pub section_order: SectionOrder,
+    /// Whether to take the size of the device tree from the reserved field
+    /// when the header has no device tree size, like some Samsung devices
+    /// store it. See `Header::dt_size_from_reserved`. The reserved field is
+    /// ignored when the device tree it describes would not fit in the source.
+    /// The device tree size is then stored in its own field when the boot
+    /// image is written. Defaults to false.
+    pub samsung_dt_quirk: bool,
+    /// Whether to reject headers whose product name or command line is not
+    /// null-terminated, with `BadHeaderError::ProductNameTooLong` or
+    /// `BadHeaderError::CmdlineTooLong`. Otherwise the whole field is used as
+    /// the string. Defaults to false.
+    pub strict_strings: bool,
+    /// Whether to read boot images that would otherwise be rejected: the magic
+    /// is not checked, and an invalid page size is replaced by the inferred
+    /// one, see `BootImage::infer_page_size`. Reading still fails when no page
+    /// size can be found. Defaults to false.
+    pub force: bool,
+    /// The byte order of the header fields. Defaults to little endian.
+    pub endianness: Endianness,
+    /// Called after the header and every section are read, with the size of
+    /// the section. Defaults to no callback.
+    pub progress: Option<Progress>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            expected_magic: MAGIC,
+            max_section_bytes: usize::MAX,
+            prealloc: true,
+            read_trailing_bytes: true,
+            section_order: SectionOrder::default(),
+            samsung_dt_quirk: false,
+            strict_strings: false,
+            force: false,
+            endianness: Endianness::Little,
+            progress: None,
+        }
+    }
+}
+
+/// Options controlling how `BootImage::write_to_opts` writes a boot image.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// The byte the padding after the header and every section is filled with.
+    /// Some vendor tools use `0xFF`, the value of erased flash. Defaults to 0.
+    pub pad_byte: u8,
+    /// Whether to write the id computed by `BootImage::compute_id` as the
+    /// unique id, instead of the one in the header. Defaults to false.
+    pub update_id: bool,
+    /// The byte order of the header fields. Defaults to little endian.
+    pub endianness: Endianness,
+    /// Called after the header and every section are written, with the size of
+    /// the section including its padding. Defaults to no callback.
+    pub progress: Option<Progress>,
+}
+
 impl Default for BootImage {
     /// Creates a new default boot image, with no sections at all.
     fn default() -> Self {
@@ -306,28 +1624,44 @@ impl Default for BootImage {
             header: Header::default(),
             kernel: Vec::new(),
             ramdisk: Vec::new(),
-            second_ramdisk: Vec::new(),
+            second: Vec::new(),
             device_tree: Vec::new(),
+            trailing_bytes: Vec::new(),
+            section_order: SectionOrder::default(),
         }
     }
 }
 
 quick_error! {
     #[derive(Debug)]
+    #[non_exhaustive]
     pub enum BadHeaderError {
         NoPageSize(header: Header) {
             description("The header does not have a page size set")
             display("The header does not have a page size set.")
         }
+        InvalidPageSize(header: Header) {
+            description("The page size is not one used by Android devices")
+            display("The page size {} is not one used by Android devices.", header.page_size)
+        }
         BadMagic(header: Header) {
             description("The header does not contain the 'ANDROID!' magic")
             display("The header does not contain the 'ANDROID!' magic.")
         }
+        ProductNameTooLong(header: Header) {
+            description("The product name does not fit in the header")
+            display("The product name does not fit in the header.")
+        }
+        CmdlineTooLong(header: Header) {
+            description("The kernel command line does not fit in the header")
+            display("The kernel command line does not fit in the header.")
+        }
     }
 }
 
 quick_error! {
     #[derive(Debug)]
+    #[non_exhaustive]
     pub enum ReadBootImageError {
         Io(cause: IoError) {
             description("An I/O error occured")
@@ -341,5 +1675,115 @@ quick_error! {
             cause(cause)
             from(cause: BadHeaderError) -> (cause)
         }
+        SectionTooLarge(section: SectionKind, size: usize, max: usize) {
+            description("A section is larger than allowed")
+            display("The {:?} section is {} bytes large, but at most {} bytes are allowed.",
+                    section, size, max)
+        }
+        UnexpectedEof { section: SectionKind, expected: usize, got: usize } {
+            description("The boot image ends in the middle of a section")
+            display("The {:?} section should be {} bytes large, but the boot image ends \
+                     after {} bytes of it.", section, expected, got)
+        }
+        InvalidPageSize(page_size: u32) {
+            description("The page size in the header is invalid")
+            display("The page size in the header is {}, which is not a valid page size.",
+                    page_size)
+        }
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum WriteSectionError {
+        Io(cause: IoError) {
+            description("An I/O error occured")
+            display("An I/O error occured.")
+            cause(cause)
+            from(cause: IoError) -> (cause)
+        }
+        SectionAbsent(section: SectionKind) {
+            description("The section is empty")
+            display("The boot image has no {:?} section.", section)
+        }
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    #[non_exhaustive]
+    pub enum WriteVerifiedError {
+        Io(cause: IoError) {
+            description("An I/O error occured")
+            display("An I/O error occured.")
+            cause(cause)
+            from(cause: IoError) -> (cause)
+        }
+        ReadBack(cause: ReadBootImageError) {
+            description("The written boot image could not be read back")
+            display("The written boot image could not be read back.")
+            cause(cause)
+            from(cause: ReadBootImageError) -> (cause)
+        }
+        RoundtripMismatch {
+            description("The written boot image differs from the original")
+            display("The boot image that was read back differs from the one that was written.")
+        }
     }
 }
+
+impl BadHeaderError {
+    /// Returns what kind of error this is.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            BadHeaderError::NoPageSize(_) => ErrorKind::NoPageSize,
+            BadHeaderError::InvalidPageSize(_) => ErrorKind::InvalidPageSize,
+            BadHeaderError::BadMagic(_) => ErrorKind::BadMagic,
+            BadHeaderError::ProductNameTooLong(_) => ErrorKind::ProductNameTooLong,
+            BadHeaderError::CmdlineTooLong(_) => ErrorKind::CmdlineTooLong,
+        }
+    }
+}
+
+impl ReadBootImageError {
+    /// Returns what kind of error this is. An I/O error caused by the end of
+    /// the source is of the `UnexpectedEof` kind, like a truncated section.
+    pub fn kind(&self) -> ErrorKind {
+        use std::io::ErrorKind as IoErrorKind;
+
+        match *self {
+            ReadBootImageError::Io(ref error) if error.kind() == IoErrorKind::UnexpectedEof => {
+                ErrorKind::UnexpectedEof
+            }
+            ReadBootImageError::Io(_) => ErrorKind::Io,
+            ReadBootImageError::BadHeader(ref error) => error.kind(),
+            ReadBootImageError::SectionTooLarge(..) => ErrorKind::SectionTooLarge,
+            ReadBootImageError::UnexpectedEof { .. } => ErrorKind::UnexpectedEof,
+            ReadBootImageError::InvalidPageSize(_) => ErrorKind::InvalidPageSize,
+        }
+    }
+}
+
+/// The kind of a `BadHeaderError` or `ReadBootImageError`. Matching on the kind
+/// keeps working when new error variants are added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An I/O error occured.
+    Io,
+    /// The source ended before the boot image did.
+    UnexpectedEof,
+    /// The header does not contain the expected magic.
+    BadMagic,
+    /// The header does not have a page size set.
+    NoPageSize,
+    /// The header has a page size that is not used by Android devices.
+    InvalidPageSize,
+    /// The product name does not fit in the header.
+    ProductNameTooLong,
+    /// The kernel command line does not fit in the header.
+    CmdlineTooLong,
+    /// A section is larger than allowed.
+    SectionTooLarge,
+}
@@ -1,9 +1,49 @@
+// The error types carry the offending header, which makes them large.
+#![allow(clippy::result_large_err)]
+
 extern crate byteorder;
+#[cfg(feature = "decompress")]
+extern crate flate2;
 #[macro_use]
 extern crate quick_error;
+extern crate sha1;
+extern crate sha2;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
+mod avb;
+mod borrowed;
+mod builder;
+mod compression;
+mod cpio;
+mod crc32;
+mod dtb;
+mod hashing_writer;
 mod header;
 mod image;
+mod progress;
+mod qcdt;
+mod reader;
+mod section;
+mod validation;
 
-pub use header::{HEADER_SIZE, Header};
-pub use image::{BadHeaderError, BootImage, ReadBootImageError};
+pub use avb::{AVB_FOOTER_SIZE, AvbFooter, extract_vbmeta, strip_avb};
+pub use borrowed::BorrowedBootImage;
+pub use builder::BootImageBuilder;
+pub use compression::Compression;
+#[cfg(feature = "decompress")]
+pub use compression::{compress, decompress};
+pub use cpio::{CpioEntry, CpioError, parse_cpio, replace_cpio_file};
+pub use dtb::DtbIter;
+pub use hashing_writer::HashingWriter;
+pub use header::{Endianness, HEADER_SIZE, Header, HeaderFieldError, LogicalHeader, MAGIC,
+                 MAGIC_SIZE, SocProfile, UniqueId, is_valid_page_size};
+pub use image::{BadHeaderError, BootImage, ErrorKind, ReadBootImageError, ReadOptions,
+                WriteOptions, WriteSectionError, WriteVerifiedError, seek_to_section};
+pub use progress::Progress;
+pub use qcdt::{QcdtEntry, QcdtError, parse_qcdt};
+pub use reader::BootImageReader;
+pub use section::{KernelFormat, ParseSectionKindError, SecondStageKind, SectionKind, SectionOrder,
+                  is_arm64_image};
+pub use validation::ValidationIssue;
@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 extern crate android_bootimage;
 #[macro_use]
 extern crate quick_error;
@@ -6,16 +8,24 @@ extern crate clap;
 extern crate colored;
 extern crate humansize;
 
-use android_bootimage::{BadHeaderError, BootImage, Header, ReadBootImageError};
+use android_bootimage::{BadHeaderError, BootImage, Compression, Header, HeaderFieldError,
+                        KernelFormat, ReadBootImageError, ReadOptions, SecondStageKind,
+                        SectionKind, WriteSectionError, extract_vbmeta, is_valid_page_size};
 use clap::{App, Arg, ArgMatches};
 use logger::{log_debug, log_error, log_error_cause, log_warning, log_warning_cause};
 use quick_error::ResultExt;
 use std::io::Error as IoError;
 use std::path::{Path, PathBuf};
 
+// `quick_error` only implements `Error::cause`, not `Error::source`.
+#[allow(deprecated)]
 fn main() {
     let result = match create_app().get_matches().subcommand() {
         ("repack", Some(arguments)) => main_repack(arguments),
+        ("verify", Some(arguments)) => main_verify(arguments),
+        ("info", Some(arguments)) => main_info(arguments),
+        ("unpack", Some(arguments)) => main_unpack(arguments),
+        ("merge", Some(arguments)) => main_merge(arguments),
         _ => panic!("No subcommand was used."),
     };
 
@@ -26,6 +36,7 @@ fn main() {
             Some(cause) => log_error_cause(format!("{}", error), cause),
             None => log_error(format!("{}", error)),
         }
+        ::std::process::exit(1);
     }
 }
 
@@ -37,6 +48,10 @@ fn create_app() -> App<'static, 'static> {
         .author(crate_authors!())
         .about("Program for handling samsung boot images.")
         .subcommand(create_app_repack())
+        .subcommand(create_app_verify())
+        .subcommand(create_app_info())
+        .subcommand(create_app_unpack())
+        .subcommand(create_app_merge())
         .max_term_width(120)
 }
 
@@ -106,17 +121,50 @@ offset and size."
                 .help("Supplies a device tree to insert into the boot image")
                 .value_name("FILE"),
         )
+        .arg(input_page_size_arg().requires("input_boot_file"))
+        .arg(force_arg().requires("input_boot_file"))
         .arg(
-            Arg::with_name("input_page_size")
-                .long("input-page-size")
-                .help("Treat the input boot image as if it had this page size")
-                .visible_aliases(&["ip", "ipage"])
+            Arg::with_name("product_name")
+                .long("product-name")
+                .help("Set the product name in the header of the boot image")
                 .long_help(
-"Treat the input boot image as if it had this page size. This switch is required if the input \
-boot image has its page size set to 0.",
+"Set the product name in the header of the boot image. The name must be shorter than 24 bytes.",
+                )
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::with_name("cmdline")
+                .long("cmdline")
+                .help("Set the kernel command line in the header of the boot image")
+                .long_help(
+"Set the kernel command line in the header of the boot image. The command line must be shorter \
+than 512 bytes.",
+                )
+                .value_name("CMDLINE"),
+        )
+        .arg(
+            Arg::with_name("append_cmdline")
+                .long("append-cmdline")
+                .help("Append arguments to the kernel command line of the boot image")
+                .long_help(
+"Append arguments to the kernel command line of the boot image, separated by a space. They are \
+appended after the command line is set with '--cmdline'. The resulting command line must be \
+shorter than 512 bytes.",
+                )
+                .value_name("ARGUMENTS"),
+        )
+        .arg(
+            Arg::with_name("strip")
+                .long("strip")
+                .help("Remove sections from the boot image")
+                .long_help(
+"Remove sections from the boot image, after the input files are inserted. Several sections can \
+be separated by commas.",
                 )
-                .value_name("INPUT_PAGE_SIZE")
-                .requires("input_boot_file")
+                .value_name("SECTION")
+                .possible_values(&["kernel", "ramdisk", "second", "dt"])
+                .use_delimiter(true)
+                .multiple(true),
         )
         .arg(
             Arg::with_name("output_boot_image_file")
@@ -157,6 +205,37 @@ boot image has its page size set to 0.",
                 .help("Extract the boot image's device tree to a file")
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::with_name("extract_vbmeta")
+                .long("extract-vbmeta")
+                .help("Extract the AVB vbmeta blob of the input boot image to a file")
+                .long_help(
+"Extract the AVB vbmeta blob of the input boot image to a file, so it can be inspected with \
+'avbtool'. The blob is only found when the input boot image ends in an AVB footer.",
+                )
+                .value_name("FILE")
+                .requires("input_boot_file"),
+        )
+        .arg(
+            Arg::with_name("concat")
+                .long("concat")
+                .help("Concatenate sections that are extracted to the same file")
+                .long_help(
+"Concatenate sections that are extracted to the same file, in the order they are stored in the \
+boot image. Without this switch, a section overwrites the sections written to the same file \
+before it.",
+                )
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .help("Validate the boot image without writing any files")
+                .long_help(
+"Validate the boot image without writing any files. The input files are read and inserted as \
+usual, after which the problems found in the resulting boot image are reported, along with the \
+files that would have been written.",
+                )
+        )
         .arg(
             Arg::with_name("output_all_default")
             .long("output-all")
@@ -176,6 +255,146 @@ Default locations:
         )
 }
 
+fn create_app_verify() -> App<'static, 'static> {
+    App::new("verify")
+        .about("Checks whether a boot image can be read, and prints the checksum of its sections.")
+        .arg(
+            Arg::with_name("input_boot_file")
+                .long("input-boot-file")
+                .visible_alias("ibf")
+                .help("Supplies the boot image to verify")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(input_page_size_arg())
+        .arg(
+            Arg::with_name("crc")
+                .long("crc")
+                .help("Verify the CRC-32 of the boot image's sections against this value")
+                .long_help(
+"Verify the CRC-32 of the boot image's sections against this value. The value is hexadecimal, \
+optionally prefixed with '0x'. The checksum covers the sections in the order they are stored in, \
+without the header and without padding.",
+                )
+                .value_name("EXPECTED")
+                .validator(|value| {
+                    parse_crc(&value)
+                        .map(|_| ())
+                        .ok_or_else(|| format!("'{}' is not a hexadecimal CRC-32", value))
+                }),
+        )
+}
+
+fn create_app_info() -> App<'static, 'static> {
+    App::new("info")
+        .about("Prints the header and the sections of a boot image.")
+        .arg(
+            Arg::with_name("input_boot_file")
+                .long("input-boot-file")
+                .visible_alias("ibf")
+                .help("Supplies the boot image to print")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(input_page_size_arg())
+        .arg(force_arg())
+}
+
+fn create_app_unpack() -> App<'static, 'static> {
+    App::new("unpack")
+        .about("Extracts all the sections of a boot image into a directory.")
+        .arg(
+            Arg::with_name("input_boot_file")
+                .long("input-boot-file")
+                .visible_alias("ibf")
+                .help("Supplies the boot image to unpack")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(input_page_size_arg())
+        .arg(
+            Arg::with_name("output_directory")
+                .long("output-directory")
+                .visible_alias("od")
+                .help("Extract the sections into this directory")
+                .long_help(
+"Extract the sections into this directory, which is created when it does not exist. Every \
+section is written to its own file: 'header.img', 'kernel.img', 'ramdisk.img', 'second.img' and \
+'dt.img'. Empty sections are skipped.",
+                )
+                .value_name("DIRECTORY")
+                .default_value("boot"),
+        )
+}
+
+fn create_app_merge() -> App<'static, 'static> {
+    App::new("merge")
+        .about("Combines the sections of several boot images into a new boot image.")
+        .arg(
+            Arg::with_name("base")
+                .long("base")
+                .help("Supplies the boot image the header and the other sections are taken from")
+                .value_name("FILE")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("kernel_from")
+                .long("kernel-from")
+                .help("Take the kernel from this boot image")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("ramdisk_from")
+                .long("ramdisk-from")
+                .help("Take the ramdisk from this boot image")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("second_from")
+                .long("second-from")
+                .help("Take the second ramdisk from this boot image")
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("dt_from")
+                .long("dt-from")
+                .help("Take the device tree from this boot image")
+                .value_name("FILE"),
+        )
+        .arg(input_page_size_arg())
+        .arg(
+            Arg::with_name("output_boot_image_file")
+                .long("output-boot-image-file")
+                .visible_alias("obf")
+                .help("Write the merged boot image to a file")
+                .value_name("FILE")
+                .required(true),
+        )
+}
+
+fn input_page_size_arg() -> Arg<'static, 'static> {
+    Arg::with_name("input_page_size")
+        .long("input-page-size")
+        .help("Treat the input boot image as if it had this page size")
+        .visible_aliases(&["ip", "ipage"])
+        .long_help(
+"Treat the input boot image as if it had this page size. This switch is required if the input \
+boot image has its page size set to 0, and the page size cannot be inferred from its length.",
+        )
+        .value_name("INPUT_PAGE_SIZE")
+}
+
+fn force_arg() -> Arg<'static, 'static> {
+    Arg::with_name("force")
+        .long("force")
+        .help("Read the input boot image even when its header is invalid")
+        .long_help(
+"Read the input boot image even when its header is invalid. A missing magic only causes a \
+warning, and an invalid page size is replaced by the one inferred from the length of the boot \
+image, unless '--input-page-size' is supplied.",
+        )
+}
+
 fn main_repack(arguments: &ArgMatches) -> Result<(), ApplicationError> {
     if arguments.is_present("input_page_size") &&
         !(arguments.is_present("input_boot_file") || arguments.is_present("input_header_file"))
@@ -185,13 +404,11 @@ fn main_repack(arguments: &ArgMatches) -> Result<(), ApplicationError> {
         );
     }
 
-    let mut boot_image = {
-        let override_page_size = arguments.value_of("input_page_size").map(|_| {
-            value_t!(arguments.value_of("input_page_size"), u32)
-                .unwrap_or_else(|error| error.exit())
-        });
-        read_boot_image(arguments.value_of("input_boot_file"), override_page_size)?
-    };
+    let mut boot_image = read_boot_image(
+        arguments.value_of("input_boot_file"),
+        input_page_size(arguments),
+        arguments.is_present("force"),
+    )?;
 
     insert_sections_from_files(
         &mut boot_image,
@@ -202,21 +419,220 @@ fn main_repack(arguments: &ArgMatches) -> Result<(), ApplicationError> {
         arguments.value_of("input_device_tree_file"),
     )?;
 
+    if let Some(product_name) = arguments.value_of("product_name") {
+        boot_image.header_mut().set_product_name(product_name)?;
+    }
+    if let Some(cmdline) = arguments.value_of("cmdline") {
+        boot_image.header_mut().set_cmdline(cmdline)?;
+    }
+    if let Some(extra_arguments) = arguments.value_of("append_cmdline") {
+        let mut cmdline = boot_image.header().cmdline();
+        if !cmdline.is_empty() {
+            cmdline.push(' ');
+        }
+        cmdline.push_str(extra_arguments);
+        boot_image.header_mut().set_cmdline(&cmdline)?;
+    }
+
+    for section in arguments.values_of("strip").into_iter().flatten() {
+        match section.parse() {
+            Ok(SectionKind::Kernel) => boot_image.clear_kernel(),
+            Ok(SectionKind::Ramdisk) => boot_image.clear_ramdisk(),
+            Ok(SectionKind::Second) => boot_image.clear_second_ramdisk(),
+            Ok(SectionKind::DeviceTree) => boot_image.clear_device_tree(),
+            _ => unreachable!("clap only accepts the names of sections"),
+        }
+    }
+
     if arguments.is_present("list_sections") {
         print_sections(&boot_image);
     }
 
+    if arguments.is_present("check") {
+        check_boot_image(
+            &boot_image,
+            &[
+                ("boot image", arguments.value_of("output_boot_image_file")),
+                ("header", arguments.value_of("output_header_file")),
+                ("kernel", arguments.value_of("output_kernel_file")),
+                ("ramdisk", arguments.value_of("output_ramdisk_file")),
+                ("second ramdisk", arguments.value_of("output_second_ramdisk_file")),
+                ("device tree", arguments.value_of("output_device_tree_file")),
+                ("vbmeta", arguments.value_of("extract_vbmeta")),
+            ],
+        );
+        return Ok(());
+    }
+
+    if let Some(path) = arguments.value_of("extract_vbmeta") {
+        // The input boot image is required by clap.
+        extract_vbmeta_into_file(arguments.value_of("input_boot_file").unwrap(), path);
+    }
+
+    if arguments.is_present("concat") {
+        extract_boot_image_into_files(
+            &boot_image,
+            arguments.value_of("output_boot_image_file"),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        extract_sections_concatenated_into_files(
+            &boot_image,
+            &[
+                (SectionKind::Header, arguments.value_of("output_header_file")),
+                (SectionKind::Kernel, arguments.value_of("output_kernel_file")),
+                (SectionKind::Ramdisk, arguments.value_of("output_ramdisk_file")),
+                (SectionKind::Second, arguments.value_of("output_second_ramdisk_file")),
+                (SectionKind::DeviceTree, arguments.value_of("output_device_tree_file")),
+            ],
+        );
+    } else {
+        extract_boot_image_into_files(
+            &boot_image,
+            arguments.value_of("output_boot_image_file"),
+            arguments.value_of("output_header_file"),
+            arguments.value_of("output_kernel_file"),
+            arguments.value_of("output_ramdisk_file"),
+            arguments.value_of("output_second_ramdisk_file"),
+            arguments.value_of("output_device_tree_file"),
+        );
+    }
+
+    Ok(())
+}
+
+fn main_verify(arguments: &ArgMatches) -> Result<(), ApplicationError> {
+    let boot_image = read_boot_image(
+        arguments.value_of("input_boot_file"),
+        input_page_size(arguments),
+        false,
+    )?;
+
+    let crc = boot_image.crc32();
+    println!("CRC-32: 0x{:08X}", crc);
+
+    // The value was already validated by clap.
+    if let Some(expected) = arguments.value_of("crc").and_then(parse_crc) {
+        if crc != expected {
+            return Err(ApplicationError::CrcMismatch(expected, crc));
+        }
+    }
+
+    Ok(())
+}
+
+fn main_info(arguments: &ArgMatches) -> Result<(), ApplicationError> {
+    let boot_image = read_boot_image(
+        arguments.value_of("input_boot_file"),
+        input_page_size(arguments),
+        arguments.is_present("force"),
+    )?;
+
+    println!("{}", boot_image.header());
+    println!();
+    print_sections(&boot_image);
+    println!();
+    print_contents(&boot_image);
+
+    Ok(())
+}
+
+fn main_unpack(arguments: &ArgMatches) -> Result<(), ApplicationError> {
+    let boot_image = read_boot_image(
+        arguments.value_of("input_boot_file"),
+        input_page_size(arguments),
+        false,
+    )?;
+
+    // The directory has a default value.
+    let directory = Path::new(arguments.value_of("output_directory").unwrap());
+    ::std::fs::create_dir_all(directory)
+        .map_err(|e| ApplicationError::CreateDirectory(directory.into(), e))?;
+
+    let path_if_present = |section: &[u8], file_name: &str| {
+        if section.is_empty() {
+            None
+        } else {
+            Some(directory.join(file_name).to_string_lossy().into_owned())
+        }
+    };
+    let kernel_path = path_if_present(boot_image.kernel(), "kernel.img");
+    let ramdisk_path = path_if_present(boot_image.ramdisk(), "ramdisk.img");
+    let second_ramdisk_path = path_if_present(boot_image.second_ramdisk(), "second.img");
+    let device_tree_path = path_if_present(boot_image.device_tree(), "dt.img");
+
+    extract_boot_image_into_files(
+        &boot_image,
+        None,
+        Some(&directory.join("header.img").to_string_lossy()),
+        kernel_path.as_deref(),
+        ramdisk_path.as_deref(),
+        second_ramdisk_path.as_deref(),
+        device_tree_path.as_deref(),
+    );
+
+    Ok(())
+}
+
+fn main_merge(arguments: &ArgMatches) -> Result<(), ApplicationError> {
+    let page_size = input_page_size(arguments);
+    let read_source = |name: &str| -> Result<Option<BootImage>, ApplicationError> {
+        match arguments.value_of(name) {
+            Some(path) => read_boot_image(Some(path), page_size, false).map(Some),
+            None => Ok(None),
+        }
+    };
+
+    // The base boot image is required by clap.
+    let mut boot_image = read_boot_image(arguments.value_of("base"), page_size, false)?;
+    if let Some(source) = read_source("kernel_from")? {
+        let (_, kernel, _, _, _) = source.into_parts();
+        boot_image.insert_kernel(kernel);
+    }
+    if let Some(source) = read_source("ramdisk_from")? {
+        let (_, _, ramdisk, _, _) = source.into_parts();
+        boot_image.insert_ramdisk(ramdisk);
+    }
+    if let Some(source) = read_source("second_from")? {
+        let (_, _, _, second, _) = source.into_parts();
+        boot_image.insert_second_ramdisk(second);
+    }
+    if let Some(source) = read_source("dt_from")? {
+        let (_, _, _, _, device_tree) = source.into_parts();
+        boot_image.insert_device_tree(device_tree);
+    }
+
     extract_boot_image_into_files(
         &boot_image,
         arguments.value_of("output_boot_image_file"),
-        arguments.value_of("output_header_file"),
-        arguments.value_of("output_kernel_file"),
-        arguments.value_of("output_ramdisk_file"),
-        arguments.value_of("output_second_ramdisk_file"),
-        arguments.value_of("output_device_tree_file"),
+        None,
+        None,
+        None,
+        None,
+        None,
     );
 
-    return Ok(());
+    Ok(())
+}
+
+/// Reads the override page size for the input boot image, exiting when it is
+/// not a valid number.
+fn input_page_size(arguments: &ArgMatches) -> Option<u32> {
+    arguments.value_of("input_page_size").map(|_| {
+        value_t!(arguments.value_of("input_page_size"), u32).unwrap_or_else(|error| error.exit())
+    })
+}
+
+/// Parses a hexadecimal CRC-32, optionally prefixed with '0x'.
+fn parse_crc(value: &str) -> Option<u32> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u32::from_str_radix(digits, 16).ok()
 }
 
 fn insert_sections_from_files(
@@ -267,6 +683,24 @@ fn insert_sections_from_files(
     Ok(())
 }
 
+/// Warn about the problems found in the boot image, and print the files that
+/// would be written, without writing them.
+fn check_boot_image(boot_image: &BootImage, outputs: &[(&str, Option<&str>)]) {
+    let issues = boot_image.validate();
+    for issue in &issues {
+        log_warning(issue.to_string());
+    }
+    if issues.is_empty() {
+        println!("The boot image has no problems.");
+    }
+
+    for &(what, path) in outputs {
+        if let Some(path) = path {
+            println!("Would write the {} to '{}'.", what, path);
+        }
+    }
+}
+
 /// Write the boot image and its sections to the specified files. Warn when a
 /// section could not be written.
 fn extract_boot_image_into_files(
@@ -299,66 +733,223 @@ fn extract_boot_image_into_files(
         );
     }
 
-    if let Some(path) = kernel_path {
-        log_result(
-            "kernel",
-            path,
-            File::create(path).and_then(|mut file| boot_image.write_kernel_to(&mut file)),
-        );
+    for &(section, path) in &[
+        (SectionKind::Kernel, kernel_path),
+        (SectionKind::Ramdisk, ramdisk_path),
+        (SectionKind::Second, second_ramdisk_path),
+        (SectionKind::DeviceTree, device_tree_path),
+    ] {
+        if let Some(path) = path {
+            extract_section_into_file(boot_image, section, path);
+        }
     }
+}
 
-    if let Some(path) = ramdisk_path {
-        log_result(
-            "ramdisk",
-            path,
-            File::create(path).and_then(|mut file| boot_image.write_ramdisk_to(&mut file)),
-        );
+/// Write a section to a file. When the section is empty, warn instead of
+/// creating an empty file.
+fn extract_section_into_file(boot_image: &BootImage, section: SectionKind, path: &str) {
+    match boot_image.write_section_checked(section, &mut LazyFile::new(path)) {
+        Ok(size) => log_result(section_name(section), path, Ok(size)),
+        Err(WriteSectionError::Io(error)) => log_result(section_name(section), path, Err(error)),
+        Err(_) => log_warning(format!(
+            "There is no {} to extract to '{}'.",
+            section_name(section),
+            path
+        )),
     }
+}
+
+/// A file that is only created when something is written to it.
+struct LazyFile<'a> {
+    path: &'a str,
+    file: Option<::std::fs::File>,
+}
+
+impl<'a> LazyFile<'a> {
+    fn new(path: &'a str) -> Self {
+        LazyFile { path, file: None }
+    }
+}
+
+impl<'a> ::std::io::Write for LazyFile<'a> {
+    fn write(&mut self, buffer: &[u8]) -> Result<usize, IoError> {
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => self.file.insert(::std::fs::File::create(self.path)?),
+        };
+        file.write(buffer)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.file.as_mut().map_or(Ok(()), |file| file.flush())
+    }
+}
+
+/// Write the chosen sections to the specified files. Sections that are written
+/// to the same file are concatenated, in the order they are stored in the boot
+/// image. Warn when a file could not be written.
+fn extract_sections_concatenated_into_files(
+    boot_image: &BootImage,
+    outputs: &[(SectionKind, Option<&str>)],
+) {
+    use std::fs::File;
+
+    let mut written_paths = Vec::new();
+    for &(_, path) in outputs {
+        let path = match path {
+            Some(path) if !written_paths.contains(&path) => path,
+            _ => continue,
+        };
+        written_paths.push(path);
+
+        let sections: Vec<SectionKind> = outputs
+            .iter()
+            .filter(|&&(_, other_path)| other_path == Some(path))
+            .map(|&(section, _)| section)
+            .collect();
+        let section_names: Vec<&str> = sections
+            .iter()
+            .map(|&section| section_name(section))
+            .collect();
 
-    if let Some(path) = second_ramdisk_path {
         log_result(
-            "second ramdisk",
+            &section_names.join("' + '"),
             path,
-            File::create(path).and_then(|mut file| boot_image.write_second_ramdisk_to(&mut file)),
+            File::create(path)
+                .and_then(|mut file| boot_image.write_sections_concatenated(&sections, &mut file)),
         );
     }
+}
 
-    if let Some(path) = device_tree_path {
-        log_result(
-            "device tree",
+fn extract_vbmeta_into_file(boot_image_path: &str, path: &str) {
+    use std::fs::File;
+    use std::io::Write;
+
+    match File::open(boot_image_path).and_then(|mut file| extract_vbmeta(&mut file)) {
+        Ok(Some(vbmeta)) => log_result(
+            "vbmeta",
             path,
-            File::create(path).and_then(|mut file| boot_image.write_device_tree_to(&mut file)),
-        );
+            File::create(path).and_then(|mut file| file.write_all(&vbmeta).map(|_| vbmeta.len())),
+        ),
+        Ok(None) => log_warning(format!(
+            "The boot image '{}' does not end in an AVB footer, so it has no vbmeta blob.",
+            boot_image_path
+        )),
+        Err(ref error) => log_warning_cause(
+            format!("Could not read the vbmeta blob from '{}'.", boot_image_path),
+            error,
+        ),
     }
+}
 
-    fn log_result(section: &str, path: &str, result: Result<usize, IoError>) {
-        use humansize::FileSize;
-        use humansize::file_size_opts::BINARY as BINARY_FILE_SIZE;
+fn section_name(section: SectionKind) -> &'static str {
+    match section {
+        SectionKind::Header => "header",
+        SectionKind::Kernel => "kernel",
+        SectionKind::Ramdisk => "ramdisk",
+        SectionKind::Second => "second ramdisk",
+        SectionKind::DeviceTree => "device tree",
+    }
+}
 
-        match result {
-            Ok(size) => log_debug(format!(
-                "Written '{}' section to '{}'. ({})",
-                section,
-                path,
-                size.file_size(BINARY_FILE_SIZE).unwrap()
-            )),
-            Err(ref error) => log_warning_cause(
-                format!("Could not write the '{}' section to '{}'.", section, path),
-                error,
-            ),
-        }
+fn log_result(section: &str, path: &str, result: Result<usize, IoError>) {
+    use humansize::FileSize;
+    use humansize::file_size_opts::BINARY as BINARY_FILE_SIZE;
+
+    match result {
+        Ok(size) => log_debug(format!(
+            "Written '{}' section to '{}'. ({})",
+            section,
+            path,
+            size.file_size(BINARY_FILE_SIZE).unwrap()
+        )),
+        Err(ref error) => log_warning_cause(
+            format!("Could not write the '{}' section to '{}'.", section, path),
+            error,
+        ),
     }
 }
 
 fn read_boot_image(
     boot_image_file: Option<&str>,
     override_page_size: Option<u32>,
+    force: bool,
 ) -> Result<BootImage, ApplicationError> {
-    match boot_image_file {
-        Some(path) => BootImage::read_from_file(path, override_page_size)
-            .context(path)
-            .map_err(|e| e.into()),
-        None => Ok(BootImage::default()),
+    use std::fs::File;
+
+    let options = ReadOptions {
+        force,
+        ..ReadOptions::default()
+    };
+    let boot_image = match boot_image_file {
+        Some(path) => File::open(path)
+            .map_err(ReadBootImageError::from)
+            .and_then(|mut file| BootImage::read_from_opts(&mut file, override_page_size, &options))
+            .context(path)?,
+        None => BootImage::default(),
+    };
+
+    if !boot_image.header().has_correct_magic() {
+        log_warning("The boot image does not have the 'ANDROID!' magic, it might not be valid.");
+    }
+
+    if !is_valid_page_size(boot_image.page_size() as u32) {
+        log_warning(format!(
+            "The page size {} is unusual, the sections might not be found.",
+            boot_image.page_size()
+        ));
+    }
+
+    Ok(boot_image)
+}
+
+/// Print what every section that is present contains, as detected from its
+/// contents.
+fn print_contents(boot_image: &BootImage) {
+    if !boot_image.kernel().is_empty() {
+        let format = match boot_image.kernel_format() {
+            KernelFormat::Arm64Efi => "arm64 (EFI)",
+            KernelFormat::Arm64Image => "arm64",
+            KernelFormat::Arm32Zimage => "arm zImage",
+            KernelFormat::Gzip => "gzip",
+            KernelFormat::Unknown => "unknown",
+        };
+        println!("kernel: {}", format);
+    }
+
+    if !boot_image.ramdisk().is_empty() {
+        println!("ramdisk: {}", compression_name(Compression::detect(boot_image.ramdisk())));
+    }
+
+    if let Some(kind) = boot_image.second_stage_kind() {
+        let kind = match kind {
+            SecondStageKind::Bootloader => "bootloader",
+            SecondStageKind::Ramdisk => "ramdisk",
+            SecondStageKind::Unknown => "unknown",
+        };
+        println!("second: {}", kind);
+    }
+
+    if !boot_image.device_tree().is_empty() {
+        match boot_image.dt_entries() {
+            Ok(entries) => println!("dt: QCDT (entries: {})", entries.len()),
+            Err(_) => match boot_image.dtbs().count() {
+                0 => println!("dt: unknown"),
+                count => println!("dt: FDT (blobs: {})", count),
+            },
+        }
+    }
+}
+
+fn compression_name(compression: Compression) -> &'static str {
+    match compression {
+        Compression::None => "uncompressed",
+        Compression::Gzip => "gzip",
+        Compression::Lz4 => "lz4",
+        Compression::Lz4Legacy => "lz4 (legacy)",
+        Compression::Xz => "xz",
+        Compression::Lzma => "lzma",
+        Compression::Bzip2 => "bzip2",
     }
 }
 
@@ -395,6 +986,7 @@ fn print_sections(bi: &BootImage) {
     }
 }
 
+#[allow(deprecated)]
 mod logger {
     use colored::Colorize;
     use std::error::Error;
@@ -415,7 +1007,7 @@ mod logger {
         eprintln!("{} {}", "warning:".bold().yellow(), message.as_ref());
     }
 
-    pub fn log_warning_cause<S: AsRef<str>>(message: S, cause: &Error) {
+    pub fn log_warning_cause<S: AsRef<str>>(message: S, cause: &dyn Error) {
         log_warning(message);
         let mut cause_opt = Some(cause);
         while let Some(cause) = cause_opt {
@@ -424,7 +1016,7 @@ mod logger {
         }
     }
 
-    pub fn log_error_cause<S: AsRef<str>>(message: S, cause: &Error) {
+    pub fn log_error_cause<S: AsRef<str>>(message: S, cause: &dyn Error) {
         log_error(message);
         let mut cause_opt = Some(cause);
         while let Some(cause) = cause_opt {
@@ -448,6 +1040,22 @@ quick_error! {
             display("Could not read the '{}' section from '{}'.", section_name, path.display())
             cause(cause)
         }
+        CrcMismatch(expected: u32, actual: u32) {
+            description("The CRC-32 of the boot image does not match.")
+            display("The CRC-32 of the boot image is 0x{:08X}, expected 0x{:08X}.",
+                    actual, expected)
+        }
+        CreateDirectory(path: PathBuf, cause: IoError) {
+            description("Could not create directory.")
+            display("Could not create the directory '{}'.", path.display())
+            cause(cause)
+        }
+        EditHeader(cause: HeaderFieldError) {
+            description("Could not edit the header.")
+            display("Could not edit the header of the boot image.")
+            cause(cause)
+            from()
+        }
         InsertHeaderError(path: PathBuf, cause: BadHeaderError) {
             description("Could not insert header into boot image.")
             display("Could not insert header from '{}' into boot image.", path.display())
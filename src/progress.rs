@@ -0,0 +1,30 @@
+use SectionKind;
+use std::fmt;
+use std::sync::Arc;
+
+/// A callback reporting the progress of reading or writing a boot image. It is
+/// called with every section that was processed, and the amount of bytes that
+/// were processed for it. Set it in `ReadOptions` or `WriteOptions`.
+#[derive(Clone)]
+pub struct Progress(Arc<dyn Fn(SectionKind, usize) + Send + Sync>);
+
+impl Progress {
+    /// Creates a progress callback from a function.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(SectionKind, usize) + Send + Sync + 'static,
+    {
+        Progress(Arc::new(callback))
+    }
+
+    /// Reports that a section was processed.
+    pub fn report(&self, section: SectionKind, bytes: usize) {
+        (self.0)(section, bytes)
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
@@ -0,0 +1,97 @@
+use byteorder::{LittleEndian, ReadBytesExt};
+
+/// The magic at the start of a QCDT table.
+const QCDT_MAGIC: &[u8; 4] = b"QCDT";
+
+/// An entry of a Qualcomm device tree table (QCDT). Every entry describes the
+/// device tree blob for one board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QcdtEntry {
+    /// The platform (SoC) id.
+    pub platform_id: u32,
+    /// The variant (hardware) id.
+    pub variant_id: u32,
+    /// The subtype id. Always 0 for version 1 tables.
+    pub subtype_id: u32,
+    /// The SoC revision.
+    pub soc_rev: u32,
+    /// The PMIC revisions. Always 0 for version 1 and 2 tables.
+    pub pmic_rev: [u32; 4],
+    /// The offset of the device tree blob, relative to the start of the table.
+    pub offset: u32,
+    /// The size of the device tree blob, in bytes.
+    pub size: u32,
+}
+
+impl QcdtEntry {
+    /// Returns the device tree blob this entry describes, given the table it
+    /// was parsed from. Returns `None` when the table is too short.
+    pub fn dtb<'a>(&self, table: &'a [u8]) -> Option<&'a [u8]> {
+        let start = self.offset as usize;
+        table.get(start..start.checked_add(self.size as usize)?)
+    }
+}
+
+/// Parses a Qualcomm device tree table (QCDT), as found in the device tree
+/// section of Qualcomm boot images. Versions 1 up to 3 are supported.
+pub fn parse_qcdt(table: &[u8]) -> Result<Vec<QcdtEntry>, QcdtError> {
+    if !table.starts_with(QCDT_MAGIC) {
+        return Err(QcdtError::BadMagic);
+    }
+
+    let mut source = &table[QCDT_MAGIC.len()..];
+    let version = source.read_u32::<LittleEndian>().map_err(|_| QcdtError::Truncated)?;
+    if !(1..=3).contains(&version) {
+        return Err(QcdtError::UnsupportedVersion(version));
+    }
+
+    let entry_count = source.read_u32::<LittleEndian>().map_err(|_| QcdtError::Truncated)?;
+    let mut entries = Vec::new();
+    for _ in 0..entry_count {
+        entries.push(read_entry(&mut source, version).map_err(|_| QcdtError::Truncated)?);
+    }
+    Ok(entries)
+}
+
+/// Helper function to read a single table entry of the given table version.
+fn read_entry(source: &mut &[u8], version: u32) -> Result<QcdtEntry, ::std::io::Error> {
+    let mut entry = QcdtEntry {
+        platform_id: source.read_u32::<LittleEndian>()?,
+        variant_id: source.read_u32::<LittleEndian>()?,
+        subtype_id: 0,
+        soc_rev: 0,
+        pmic_rev: [0; 4],
+        offset: 0,
+        size: 0,
+    };
+    if version >= 2 {
+        entry.subtype_id = source.read_u32::<LittleEndian>()?;
+    }
+    entry.soc_rev = source.read_u32::<LittleEndian>()?;
+    if version >= 3 {
+        for pmic_rev in entry.pmic_rev.iter_mut() {
+            *pmic_rev = source.read_u32::<LittleEndian>()?;
+        }
+    }
+    entry.offset = source.read_u32::<LittleEndian>()?;
+    entry.size = source.read_u32::<LittleEndian>()?;
+    Ok(entry)
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum QcdtError {
+        BadMagic {
+            description("The device tree does not contain the 'QCDT' magic")
+            display("The device tree does not contain the 'QCDT' magic.")
+        }
+        UnsupportedVersion(version: u32) {
+            description("The device tree table version is not supported")
+            display("Version {} device tree tables are not supported.", version)
+        }
+        Truncated {
+            description("The device tree table is truncated")
+            display("The device tree table is truncated.")
+        }
+    }
+}
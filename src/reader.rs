@@ -0,0 +1,86 @@
+use BootImage;
+use header::HEADER_SIZE;
+use image::size_to_size_in_pages;
+use std::cmp;
+use std::io::{Read, Result as IoResult};
+
+/// A reader streaming a boot image in the layout written by
+/// `BootImage::write_to`. The header and the sections are copied out of
+/// the boot image as they are read, so the serialized boot image is never held
+/// in memory as a whole. Created with `BootImage::reader`.
+#[derive(Debug, Clone)]
+pub struct BootImageReader<'a> {
+    /// The boot image being streamed.
+    boot_image: &'a BootImage,
+    /// The serialized header.
+    header: [u8; HEADER_SIZE],
+    /// The part being read: the header, the sections in the order they are
+    /// stored in, and finally the trailing bytes.
+    part: usize,
+    /// The position in the current part, including its padding.
+    position: usize,
+}
+
+impl<'a> BootImageReader<'a> {
+    /// Creates a reader streaming the boot image from its start.
+    pub fn new(boot_image: &'a BootImage) -> Self {
+        let mut header = [0; HEADER_SIZE];
+        // The buffer is exactly large enough for the header.
+        boot_image.header().write_to(&mut &mut header[..]).unwrap();
+
+        BootImageReader {
+            boot_image,
+            header,
+            part: 0,
+            position: 0,
+        }
+    }
+
+    /// Helper function to get the data of the current part, and its size
+    /// including the padding. Returns `None` after the last part.
+    fn current_part(&self) -> Option<(&[u8], usize)> {
+        let page_size = self.boot_image.page_size();
+        let padded_size = |size| size_to_size_in_pages(size, page_size) * page_size;
+        let order = self.boot_image.section_order();
+        let sections = order.sections();
+
+        if self.part == 0 {
+            Some((&self.header[..], padded_size(HEADER_SIZE)))
+        } else if self.part <= sections.len() {
+            let data = self.boot_image.section_data(sections[self.part - 1]);
+            Some((data, padded_size(data.len())))
+        } else if self.part == sections.len() + 1 {
+            let data = self.boot_image.trailing_bytes();
+            Some((data, data.len()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a> Read for BootImageReader<'a> {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        loop {
+            let length = match self.current_part() {
+                None => return Ok(0),
+                Some((_, size)) if self.position >= size => 0,
+                Some((data, size)) => {
+                    let length = cmp::min(buffer.len(), size - self.position);
+                    for (index, byte) in buffer[..length].iter_mut().enumerate() {
+                        // Past the end of the data is padding.
+                        *byte = data.get(self.position + index).cloned().unwrap_or(0);
+                    }
+                    length
+                }
+            };
+
+            if length == 0 && !buffer.is_empty() {
+                self.part += 1;
+                self.position = 0;
+            } else {
+                self.position += length;
+                return Ok(length);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use Compression;
+use std::str::FromStr;
+
+/// Identifies one of the sections of a boot image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    /// The header.
+    Header,
+    /// The kernel.
+    Kernel,
+    /// The ramdisk.
+    Ramdisk,
+    /// The optional second file, usually a second ramdisk.
+    Second,
+    /// The device tree.
+    DeviceTree,
+}
+
+impl SectionKind {
+    /// Returns the short name of the section, as used on the command line:
+    /// `"header"`, `"kernel"`, `"ramdisk"`, `"second"` or `"dt"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SectionKind::Header => "header",
+            SectionKind::Kernel => "kernel",
+            SectionKind::Ramdisk => "ramdisk",
+            SectionKind::Second => "second",
+            SectionKind::DeviceTree => "dt",
+        }
+    }
+}
+
+impl FromStr for SectionKind {
+    type Err = ParseSectionKindError;
+
+    /// Parses the short name of a section, as returned by `name`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "header" => Ok(SectionKind::Header),
+            "kernel" => Ok(SectionKind::Kernel),
+            "ramdisk" => Ok(SectionKind::Ramdisk),
+            "second" => Ok(SectionKind::Second),
+            "dt" => Ok(SectionKind::DeviceTree),
+            _ => Err(ParseSectionKindError::UnknownSection(name.to_owned())),
+        }
+    }
+}
+
+/// The order the sections of a boot image are stored in, after the header.
+/// Some vendors store the device tree before the second section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SectionOrder([SectionKind; 4]);
+
+impl SectionOrder {
+    /// The order used by AOSP's `mkbootimg`: the kernel, the ramdisk, the
+    /// second section and the device tree.
+    pub const STANDARD: SectionOrder = SectionOrder([
+        SectionKind::Kernel,
+        SectionKind::Ramdisk,
+        SectionKind::Second,
+        SectionKind::DeviceTree,
+    ]);
+
+    /// Creates an order from the sections following the header. Returns `None`
+    /// unless every section besides the header appears exactly once.
+    pub fn new(sections: [SectionKind; 4]) -> Option<Self> {
+        if SectionOrder::STANDARD.0.iter().all(|section| sections.contains(section)) {
+            Some(SectionOrder(sections))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the sections following the header, in the order they are
+    /// stored in.
+    pub fn sections(&self) -> &[SectionKind; 4] {
+        &self.0
+    }
+}
+
+impl Default for SectionOrder {
+    fn default() -> Self {
+        SectionOrder::STANDARD
+    }
+}
+
+/// What the optional second section of a boot image contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecondStageKind {
+    /// A second stage bootloader, stored as an ELF executable.
+    Bootloader,
+    /// A ramdisk, either a plain cpio archive or a compressed one.
+    Ramdisk,
+    /// Anything else.
+    Unknown,
+}
+
+impl SecondStageKind {
+    /// Detects what a second section contains by looking at its magic.
+    pub fn detect(data: &[u8]) -> Self {
+        const ELF_MAGIC: &[u8] = b"\x7FELF";
+        const CPIO_MAGICS: &[&[u8]] = &[
+            b"070701",             // newc format
+            b"070702",             // newc format with checksums
+        ];
+
+        if data.starts_with(ELF_MAGIC) {
+            SecondStageKind::Bootloader
+        } else if CPIO_MAGICS.iter().any(|magic| data.starts_with(magic)) ||
+                   Compression::detect(data) != Compression::None {
+            SecondStageKind::Ramdisk
+        } else {
+            SecondStageKind::Unknown
+        }
+    }
+}
+
+/// The format of a kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KernelFormat {
+    /// An ARM64 `Image` that can also be booted as an EFI application, as it
+    /// starts with the 'MZ' magic of a PE executable.
+    Arm64Efi,
+    /// A plain ARM64 `Image`.
+    Arm64Image,
+    /// An ARM `zImage`.
+    Arm32Zimage,
+    /// A gzip compressed kernel, like an `Image.gz`.
+    Gzip,
+    /// Anything else.
+    Unknown,
+}
+
+impl KernelFormat {
+    /// Detects the format of a kernel by looking at the magics in its header.
+    pub fn detect(kernel: &[u8]) -> Self {
+        const PE_MAGIC: &[u8] = b"MZ";
+        // The magic of a `zImage`, 0x016F2818, stored little-endian at 0x24.
+        const ZIMAGE_MAGIC: &[u8] = b"\x18\x28\x6F\x01";
+
+        if is_arm64_image(kernel) {
+            if kernel.starts_with(PE_MAGIC) {
+                KernelFormat::Arm64Efi
+            } else {
+                KernelFormat::Arm64Image
+            }
+        } else if kernel.get(0x24..0x28) == Some(ZIMAGE_MAGIC) {
+            KernelFormat::Arm32Zimage
+        } else if Compression::detect(kernel) == Compression::Gzip {
+            KernelFormat::Gzip
+        } else {
+            KernelFormat::Unknown
+        }
+    }
+}
+
+/// Returns whether a kernel is an uncompressed ARM64 `Image`, by looking for the
+/// 'ARM\x64' magic in its header. Such kernels usually start with 'MZ' too, as
+/// they can be booted as an EFI application.
+pub fn is_arm64_image(kernel: &[u8]) -> bool {
+    kernel.get(56..60) == Some(&b"ARM\x64"[..])
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum ParseSectionKindError {
+        UnknownSection(name: String) {
+            description("Not the name of a section")
+            display("'{}' is not the name of a section.", name)
+        }
+    }
+}
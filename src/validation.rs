@@ -0,0 +1,68 @@
+use SectionKind;
+use std::fmt;
+
+/// A problem found in a boot image. Unlike an error, an issue does not prevent
+/// the boot image from being used, but might make it unusable on a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// The header could not be read at all.
+    UnreadableHeader,
+    /// The header does not contain the expected magic, which is usually
+    /// 'ANDROID!'.
+    BadMagic,
+    /// The header does not have a page size set.
+    NoPageSize,
+    /// The page size is not a power of two between 2048 and 16384 bytes.
+    InvalidPageSize(u32),
+    /// The size of the section in the header does not match its actual size.
+    SizeMismatch {
+        section: SectionKind,
+        header_size: u32,
+        actual_size: usize,
+    },
+    /// The load address of the section is not aligned to the page size.
+    UnalignedLoadAddress { section: SectionKind, address: u32 },
+    /// The source ended before the section did. `got` bytes of the `expected`
+    /// bytes are available.
+    TruncatedSection {
+        section: SectionKind,
+        expected: usize,
+        got: usize,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationIssue::UnreadableHeader => write!(f, "The header could not be read."),
+            ValidationIssue::BadMagic => write!(f, "The header does not have the expected magic."),
+            ValidationIssue::NoPageSize => write!(f, "The header does not have a page size set."),
+            ValidationIssue::InvalidPageSize(page_size) => {
+                write!(f, "The page size {} is not valid.", page_size)
+            }
+            ValidationIssue::SizeMismatch {
+                section,
+                header_size,
+                actual_size,
+            } => write!(
+                f,
+                "The {:?} section is {} bytes large, but the header says {} bytes.",
+                section, actual_size, header_size
+            ),
+            ValidationIssue::UnalignedLoadAddress { section, address } => write!(
+                f,
+                "The load address 0x{:08x} of the {:?} section is not aligned to the page size.",
+                address, section
+            ),
+            ValidationIssue::TruncatedSection {
+                section,
+                expected,
+                got,
+            } => write!(
+                f,
+                "The {:?} section should be {} bytes large, but only {} bytes are available.",
+                section, expected, got
+            ),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+extern crate android_bootimage;
+
+use android_bootimage::{AVB_FOOTER_SIZE, AvbFooter, BootImageBuilder, extract_vbmeta, strip_avb};
+use std::io::Cursor;
+
+/// Returns a boot image followed by a vbmeta blob, padding, and an AVB footer
+/// describing them.
+fn footered_image(original: &[u8], vbmeta: &[u8]) -> Vec<u8> {
+    let mut image = original.to_vec();
+    image.extend_from_slice(vbmeta);
+    image.extend_from_slice(&[0; 4096]);
+
+    let mut footer = b"AVBf".to_vec();
+    footer.extend_from_slice(&1u32.to_be_bytes());
+    footer.extend_from_slice(&0u32.to_be_bytes());
+    footer.extend_from_slice(&(original.len() as u64).to_be_bytes());
+    footer.extend_from_slice(&(original.len() as u64).to_be_bytes());
+    footer.extend_from_slice(&(vbmeta.len() as u64).to_be_bytes());
+    footer.resize(AVB_FOOTER_SIZE, 0);
+    image.extend_from_slice(&footer);
+    image
+}
+
+fn sample_bytes() -> Vec<u8> {
+    BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .build()
+        .unwrap()
+        .to_bytes()
+}
+
+#[test]
+fn read_footer() {
+    let original = sample_bytes();
+    let image = footered_image(&original, b"vbmeta");
+    let footer = AvbFooter::read_from(&mut Cursor::new(&image)).unwrap().unwrap();
+    assert_eq!(footer.version_major, 1);
+    assert_eq!(footer.original_image_size, original.len() as u64);
+    assert_eq!(footer.vbmeta_size, 6);
+
+    assert_eq!(AvbFooter::read_from(&mut Cursor::new(&original)).unwrap(), None);
+}
+
+#[test]
+fn strip_footered_image() {
+    let original = sample_bytes();
+    let image = footered_image(&original, b"vbmeta");
+    assert_eq!(strip_avb(&mut Cursor::new(&image)).unwrap(), original);
+}
+
+#[test]
+fn strip_image_without_footer() {
+    let original = sample_bytes();
+    assert_eq!(strip_avb(&mut Cursor::new(&original)).unwrap(), original);
+}
+
+#[test]
+fn strip_rejects_oversized_original_size() {
+    let original = sample_bytes();
+    let mut image = footered_image(&original, b"vbmeta");
+    let footer_start = image.len() - AVB_FOOTER_SIZE;
+    image[footer_start + 12..footer_start + 20].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(strip_avb(&mut Cursor::new(&image)).is_err());
+}
+
+#[test]
+fn extract_embedded_vbmeta() {
+    let original = sample_bytes();
+    let vbmeta = b"AVB0 vbmeta blob".to_vec();
+    let image = footered_image(&original, &vbmeta);
+    assert_eq!(extract_vbmeta(&mut Cursor::new(&image)).unwrap(), Some(vbmeta));
+    assert_eq!(extract_vbmeta(&mut Cursor::new(&original)).unwrap(), None);
+}
+
+#[test]
+fn extract_rejects_vbmeta_outside_of_file() {
+    let mut image = footered_image(&sample_bytes(), b"vbmeta");
+    let footer_start = image.len() - AVB_FOOTER_SIZE;
+    image[footer_start + 28..footer_start + 36].copy_from_slice(&u64::MAX.to_be_bytes());
+    assert!(extract_vbmeta(&mut Cursor::new(&image)).is_err());
+}
@@ -0,0 +1,71 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImage, BootImageBuilder, BorrowedBootImage};
+
+fn sample_image() -> BootImage {
+    BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .ramdisk(vec![2; 100])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn sections_borrow_from_the_buffer() {
+    let bytes = sample_image().to_bytes();
+    let borrowed = BorrowedBootImage::from_slice(&bytes, None).unwrap();
+    assert_eq!(borrowed.kernel().as_ptr(), bytes[2048..].as_ptr());
+    assert_eq!(borrowed.ramdisk().as_ptr(), bytes[3 * 2048..].as_ptr());
+    assert_eq!(borrowed.kernel(), &[1; 3000][..]);
+    assert_eq!(borrowed.ramdisk(), &[2; 100][..]);
+    assert!(borrowed.second_ramdisk().is_empty());
+}
+
+#[test]
+fn offsets_match_the_owned_image() {
+    let boot_image = sample_image();
+    let bytes = boot_image.to_bytes();
+    let borrowed = BorrowedBootImage::from_slice(&bytes, None).unwrap();
+    assert_eq!(borrowed.kernel_offset(), boot_image.kernel_offset());
+    assert_eq!(borrowed.ramdisk_offset(), boot_image.ramdisk_offset());
+    assert_eq!(borrowed.second_ramdisk_offset(), boot_image.second_ramdisk_offset());
+    assert_eq!(borrowed.device_tree_offset(), boot_image.device_tree_offset());
+    assert_eq!(borrowed.kernel_size_in_pages(), 2);
+}
+
+#[test]
+fn truncated_buffer() {
+    let bytes = sample_image().to_bytes();
+    assert!(BorrowedBootImage::from_slice(&bytes[..3000], None).is_err());
+}
+
+#[test]
+fn offsets_match_for_all_sections() {
+    for &page_size in &[2048, 4096] {
+        for &(kernel, ramdisk, second, device_tree) in
+            &[(1, 1, 1, 1), (4096, 0, 10, 5000), (5000, 8193, 0, 0)]
+        {
+            let mut boot_image = BootImageBuilder::new()
+                .kernel(vec![1; kernel])
+                .ramdisk(vec![2; ramdisk])
+                .page_size(page_size)
+                .build()
+                .unwrap();
+            boot_image.insert_second_ramdisk(vec![3; second]);
+            boot_image.insert_device_tree(vec![4; device_tree]);
+            let bytes = boot_image.to_bytes();
+            let borrowed = BorrowedBootImage::from_slice(&bytes, None).unwrap();
+
+            assert_eq!(borrowed.kernel_offset(), boot_image.kernel_offset());
+            assert_eq!(borrowed.ramdisk_offset(), boot_image.ramdisk_offset());
+            assert_eq!(borrowed.second_ramdisk_offset(), boot_image.second_ramdisk_offset());
+            assert_eq!(borrowed.device_tree_offset(), boot_image.device_tree_offset());
+            assert_eq!(
+                borrowed.device_tree_size_in_pages(),
+                boot_image.device_tree_size_in_pages()
+            );
+            assert_eq!(borrowed.second_ramdisk(), boot_image.second_ramdisk());
+            assert_eq!(borrowed.device_tree(), boot_image.device_tree());
+        }
+    }
+}
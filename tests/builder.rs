@@ -0,0 +1,51 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BadHeaderError, BootImage, BootImageBuilder};
+use std::io::Cursor;
+
+#[test]
+fn build_and_read_back() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .ramdisk(vec![2; 100])
+        .page_size(4096)
+        .kernel_load_address(0x80008000)
+        .cmdline("console=ttyS0")
+        .product_name("test")
+        .build()
+        .unwrap();
+    assert_eq!(boot_image.header().kernel_size, 3000);
+    assert_eq!(boot_image.header().ramdisk_size, 100);
+
+    let mut bytes = Vec::new();
+    boot_image.write_to(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 3 * 4096);
+
+    let read = BootImage::read_from(&mut Cursor::new(&bytes), None).unwrap();
+    assert_eq!(read.kernel(), &[1; 3000][..]);
+    assert_eq!(read.ramdisk(), &[2; 100][..]);
+    assert_eq!(read.page_size(), 4096);
+    assert_eq!(read.header().kernel_load_address, 0x80008000);
+    assert_eq!(read.header().cmdline(), "console=ttyS0");
+    assert_eq!(read.header().product_name_str(), "test");
+}
+
+#[test]
+fn build_rejects_invalid_headers() {
+    match BootImageBuilder::new().page_size(0).build() {
+        Err(BadHeaderError::NoPageSize(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    match BootImageBuilder::new().page_size(3000).build() {
+        Err(BadHeaderError::InvalidPageSize(ref header)) if header.page_size == 3000 => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    match BootImageBuilder::new().product_name(&"x".repeat(24)).build() {
+        Err(BadHeaderError::ProductNameTooLong(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    match BootImageBuilder::new().cmdline(&"x".repeat(512)).build() {
+        Err(BadHeaderError::CmdlineTooLong(_)) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,358 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImage, BootImageBuilder};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+
+/// Creates an empty directory for a single test.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("android-bootimage-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a small boot image into the directory, returning its path.
+fn write_sample_image(dir: &Path) -> String {
+    let boot_image = BootImageBuilder::new()
+        .kernel(b"1234".to_vec())
+        .ramdisk(b"56789".to_vec())
+        .build()
+        .unwrap();
+    let path = dir.join("boot.img");
+    fs::write(&path, boot_image.to_bytes()).unwrap();
+    path.to_str().unwrap().into()
+}
+
+fn run(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_android-bootimage"))
+        .args(arguments)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn verify_crc() {
+    let dir = test_dir("verify-crc");
+    let image = write_sample_image(&dir);
+
+    let output = run(&["verify", "--ibf", &image, "--crc", "0xCBF43926"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("CRC-32: 0xCBF43926"));
+
+    let output = run(&["verify", "--ibf", &image, "--crc", "12345678"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn repack_concat() {
+    let dir = test_dir("repack-concat");
+    let image = write_sample_image(&dir);
+    let combined = dir.join("combined.img");
+    let combined = combined.to_str().unwrap();
+
+    let output = run(&["repack", "--ibf", &image, "--okf", combined, "--orf", combined]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(combined).unwrap(), b"56789");
+
+    let output =
+        run(&["repack", "--ibf", &image, "--okf", combined, "--orf", combined, "--concat"]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(combined).unwrap(), b"123456789");
+}
+
+#[test]
+fn repack_check_creates_no_files() {
+    let dir = test_dir("repack-check");
+    let image = write_sample_image(&dir);
+    let output_image = dir.join("out.img");
+    let output_kernel = dir.join("out").join("kernel.img");
+
+    let output = run(&[
+        "repack",
+        "--ibf",
+        &image,
+        "--obf",
+        output_image.to_str().unwrap(),
+        "--okf",
+        output_kernel.to_str().unwrap(),
+        "--check",
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Would write the boot image to"));
+    assert!(stdout.contains("Would write the kernel to"));
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+/// Writes a boot image with a page size of 4096 set to 0 in its header,
+/// followed by bytes that keep the page size from being inferred.
+fn write_zero_page_image(dir: &Path) -> String {
+    let boot_image = BootImageBuilder::new()
+        .kernel(b"1234".to_vec())
+        .page_size(4096)
+        .build()
+        .unwrap();
+    let mut bytes = boot_image.to_bytes();
+    let mut header = boot_image.header().clone();
+    header.page_size = 0;
+    header.write_to(&mut &mut bytes[..]).unwrap();
+    bytes.extend_from_slice(&[0xFF; 100]);
+
+    let path = dir.join("zero-page.img");
+    fs::write(&path, bytes).unwrap();
+    path.to_str().unwrap().into()
+}
+
+#[test]
+fn info_and_unpack_page_size_override() {
+    let dir = test_dir("page-size-override");
+    let image = write_zero_page_image(&dir);
+
+    assert!(!run(&["info", "--ibf", &image]).status.success());
+    let output = run(&["info", "--ibf", &image, "--input-page-size", "4096"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("4096"));
+
+    let unpacked = dir.join("unpacked");
+    let unpacked = unpacked.to_str().unwrap();
+    assert!(!run(&["unpack", "--ibf", &image, "--od", unpacked]).status.success());
+    let output = run(&["unpack", "--ibf", &image, "--od", unpacked, "--ip", "4096"]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("unpacked").join("kernel.img")).unwrap(), b"1234");
+}
+
+#[test]
+fn repack_product_name() {
+    let dir = test_dir("repack-product-name");
+    let image = write_sample_image(&dir);
+    let output_image = dir.join("out.img");
+    let output_image = output_image.to_str().unwrap();
+    let repack = |product_name: &str| {
+        run(&[
+            "repack",
+            "--ibf",
+            &image,
+            "--product-name",
+            product_name,
+            "--obf",
+            output_image,
+        ])
+    };
+
+    assert!(repack("SM-G935F").status.success());
+    let boot_image = BootImage::read_from_file(output_image, None).unwrap();
+    assert_eq!(boot_image.header().product_name_str(), "SM-G935F");
+    assert_eq!(boot_image.kernel(), b"1234");
+
+    assert_eq!(repack(&"x".repeat(24)).status.code(), Some(1));
+}
+
+#[test]
+fn repack_cmdline() {
+    let dir = test_dir("repack-cmdline");
+    let image = write_sample_image(&dir);
+    let output_image = dir.join("out.img");
+    let output_image = output_image.to_str().unwrap();
+
+    let output = run(&[
+        "repack",
+        "--ibf",
+        &image,
+        "--cmdline",
+        "console=ttyS0 quiet",
+        "--append-cmdline",
+        "androidboot.selinux=permissive",
+        "--obf",
+        output_image,
+    ]);
+    assert!(output.status.success());
+    let boot_image = BootImage::read_from_file(output_image, None).unwrap();
+    assert_eq!(
+        boot_image.header().cmdline(),
+        "console=ttyS0 quiet androidboot.selinux=permissive"
+    );
+
+    let output = run(&[
+        "repack",
+        "--ibf",
+        output_image,
+        "--append-cmdline",
+        "loglevel=7",
+        "--obf",
+        output_image,
+    ]);
+    assert!(output.status.success());
+    let boot_image = BootImage::read_from_file(output_image, None).unwrap();
+    assert!(boot_image.header().cmdline().ends_with("permissive loglevel=7"));
+
+    let cmdline = "x".repeat(512);
+    let output = run(&["repack", "--ibf", &image, "--cmdline", &cmdline, "--obf", output_image]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn repack_strip() {
+    let dir = test_dir("repack-strip");
+    let mut boot_image = BootImageBuilder::new()
+        .kernel(b"1234".to_vec())
+        .ramdisk(b"56789".to_vec())
+        .build()
+        .unwrap();
+    boot_image.insert_second_ramdisk(b"second".to_vec());
+    boot_image.insert_device_tree(b"dt".to_vec());
+    let image = dir.join("boot.img");
+    fs::write(&image, boot_image.to_bytes()).unwrap();
+    let image = image.to_str().unwrap();
+    let output_image = dir.join("out.img");
+    let output_image = output_image.to_str().unwrap();
+
+    let output = run(&["repack", "--ibf", image, "--strip", "second", "--obf", output_image]);
+    assert!(output.status.success());
+    let read = BootImage::read_from_file(output_image, None).unwrap();
+    assert!(read.second_ramdisk().is_empty());
+    assert_eq!(read.header().second_size, 0);
+    assert_eq!(read.device_tree(), b"dt");
+    assert_eq!(read.kernel(), b"1234");
+
+    let output = run(&["repack", "--ibf", image, "--strip", "second,dt", "--obf", output_image]);
+    assert!(output.status.success());
+    let read = BootImage::read_from_file(output_image, None).unwrap();
+    assert!(read.second_ramdisk().is_empty());
+    assert!(read.device_tree().is_empty());
+
+    let output = run(&["repack", "--ibf", image, "--strip", "bogus", "--obf", output_image]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn repack_extract_vbmeta() {
+    let dir = test_dir("repack-extract-vbmeta");
+    let image = write_sample_image(&dir);
+    let mut footered = fs::read(&image).unwrap();
+    let original_size = footered.len() as u64;
+    footered.extend_from_slice(b"vbmeta blob");
+    let mut footer = b"AVBf".to_vec();
+    footer.extend_from_slice(&1u32.to_be_bytes());
+    footer.extend_from_slice(&0u32.to_be_bytes());
+    footer.extend_from_slice(&original_size.to_be_bytes());
+    footer.extend_from_slice(&original_size.to_be_bytes());
+    footer.extend_from_slice(&11u64.to_be_bytes());
+    footer.resize(64, 0);
+    footered.extend_from_slice(&footer);
+    fs::write(&image, footered).unwrap();
+    let vbmeta = dir.join("vbmeta.img");
+    let vbmeta = vbmeta.to_str().unwrap();
+
+    let output = run(&["repack", "--ibf", &image, "--extract-vbmeta", vbmeta]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(vbmeta).unwrap(), b"vbmeta blob");
+}
+
+#[test]
+fn merge() {
+    let dir = test_dir("merge");
+    let write_image = |name: &str, kernel: &[u8], ramdisk: &[u8], cmdline: &str| {
+        let boot_image = BootImageBuilder::new()
+            .kernel(kernel.to_vec())
+            .ramdisk(ramdisk.to_vec())
+            .cmdline(cmdline)
+            .build()
+            .unwrap();
+        let path = dir.join(name);
+        fs::write(&path, boot_image.to_bytes()).unwrap();
+        path.to_str().unwrap().to_owned()
+    };
+    let image_a = write_image("a.img", b"kernel a", b"ramdisk a", "console=a");
+    let image_b = write_image("b.img", b"kernel b", b"a longer ramdisk b", "console=b");
+    let output_image = dir.join("merged.img");
+    let output_image = output_image.to_str().unwrap();
+
+    let output = run(&[
+        "merge",
+        "--base",
+        &image_a,
+        "--ramdisk-from",
+        &image_b,
+        "--obf",
+        output_image,
+    ]);
+    assert!(output.status.success());
+    let merged = BootImage::read_from_file(output_image, None).unwrap();
+    assert_eq!(merged.kernel(), b"kernel a");
+    assert_eq!(merged.ramdisk(), b"a longer ramdisk b");
+    assert_eq!(merged.header().ramdisk_size, 18);
+    assert_eq!(merged.header().cmdline(), "console=a");
+
+    let output = run(&["merge", "--ramdisk-from", &image_b, "--obf", output_image]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn repack_skips_empty_sections() {
+    let dir = test_dir("repack-empty-section");
+    let image = write_sample_image(&dir);
+    let ramdisk = dir.join("ramdisk.img");
+    let device_tree = dir.join("dt.img");
+
+    let output = run(&[
+        "repack",
+        "--ibf",
+        &image,
+        "--orf",
+        ramdisk.to_str().unwrap(),
+        "--odf",
+        device_tree.to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(&ramdisk).unwrap(), b"56789");
+    assert!(!device_tree.exists());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There is no device tree"));
+}
+
+#[test]
+fn repack_force() {
+    let dir = test_dir("repack-force");
+    let image = write_sample_image(&dir);
+    let mut bytes = fs::read(&image).unwrap();
+    bytes[..8].copy_from_slice(b"NOTANIMG");
+    fs::write(&image, bytes).unwrap();
+    let kernel = dir.join("kernel.img");
+    let kernel = kernel.to_str().unwrap();
+
+    let output = run(&["repack", "--ibf", &image, "--okf", kernel]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run(&["repack", "--force", "--ibf", &image, "--okf", kernel]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(kernel).unwrap(), b"1234");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'ANDROID!' magic"));
+}
+
+#[test]
+fn info_contents() {
+    let dir = test_dir("info-contents");
+    let mut kernel = vec![0; 64];
+    kernel[56..60].copy_from_slice(b"ARM\x64");
+    let mut device_tree = vec![0; 40];
+    device_tree[..4].copy_from_slice(&[0xd0, 0x0d, 0xfe, 0xed]);
+    device_tree[4..8].copy_from_slice(&40u32.to_be_bytes());
+    let mut boot_image = BootImageBuilder::new()
+        .kernel(kernel)
+        .ramdisk(vec![0x1F, 0x8B, 0x08, 0x00, 0, 0])
+        .build()
+        .unwrap();
+    boot_image.insert_device_tree(device_tree);
+    let image = dir.join("boot.img");
+    fs::write(&image, boot_image.to_bytes()).unwrap();
+
+    let output = run(&["info", "--ibf", image.to_str().unwrap()]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("kernel: arm64\n"));
+    assert!(stdout.contains("ramdisk: gzip\n"));
+    assert!(stdout.contains("dt: FDT (blobs: 1)\n"));
+    assert!(!stdout.contains("second:"));
+}
@@ -0,0 +1,69 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImageBuilder, Compression};
+#[cfg(feature = "decompress")]
+use android_bootimage::{BootImage, compress, decompress};
+#[cfg(feature = "decompress")]
+use std::io::Cursor;
+
+/// The start of an ARM64 `Image` kernel that is also an EFI application.
+fn arm64_kernel() -> Vec<u8> {
+    let mut kernel = vec![0; 64];
+    kernel[..2].copy_from_slice(b"MZ");
+    kernel[56..60].copy_from_slice(b"ARM\x64");
+    kernel
+}
+
+#[test]
+fn detect() {
+    assert_eq!(Compression::detect(&[0x1F, 0x8B, 0x08]), Compression::Gzip);
+    assert_eq!(Compression::detect(b"\x02\x21\x4C\x18"), Compression::Lz4Legacy);
+    assert_eq!(Compression::detect(b"\xFD7zXZ\x00"), Compression::Xz);
+    assert_eq!(Compression::detect(b"BZh9"), Compression::Bzip2);
+    assert_eq!(Compression::detect(&arm64_kernel()), Compression::None);
+}
+
+#[test]
+fn kernel_compression() {
+    let boot_image = BootImageBuilder::new().kernel(arm64_kernel()).build().unwrap();
+    assert_eq!(boot_image.kernel_compression(), Compression::None);
+
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![0x1F, 0x8B, 0x08, 0x00])
+        .build()
+        .unwrap();
+    assert_eq!(boot_image.kernel_compression(), Compression::Gzip);
+}
+
+#[cfg(feature = "decompress")]
+#[test]
+fn decompress_kernel() {
+    let compressed = compress(&arm64_kernel(), Compression::Gzip).unwrap();
+    assert_eq!(Compression::detect(&compressed), Compression::Gzip);
+    let boot_image = BootImageBuilder::new().kernel(compressed).build().unwrap();
+    let kernel = boot_image.decompress_kernel().unwrap();
+    assert_eq!(kernel, arm64_kernel());
+    assert!(kernel.starts_with(b"MZ"));
+
+    // Kernels that are not compressed are returned as-is.
+    assert_eq!(decompress(&arm64_kernel()).unwrap(), arm64_kernel());
+    assert!(decompress(b"BZh9").is_err());
+}
+
+#[cfg(feature = "decompress")]
+#[test]
+fn read_compressed_image() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(arm64_kernel())
+        .ramdisk(vec![2; 100])
+        .build()
+        .unwrap();
+    let bytes = boot_image.to_bytes();
+    let compressed = compress(&bytes, Compression::Gzip).unwrap();
+    assert!(compressed.len() < bytes.len());
+
+    let read = BootImage::read_from_compressed(&mut Cursor::new(&compressed), None).unwrap();
+    assert!(read == boot_image);
+    let read = BootImage::read_from_compressed(&mut Cursor::new(&bytes), None).unwrap();
+    assert!(read == boot_image);
+}
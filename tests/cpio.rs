@@ -0,0 +1,116 @@
+extern crate android_bootimage;
+
+#[cfg(feature = "decompress")]
+use android_bootimage::{BootImageBuilder, Compression, compress};
+use android_bootimage::{CpioEntry, CpioError, parse_cpio, replace_cpio_file};
+
+/// Appends an entry in the newc format to an archive.
+fn push_entry(archive: &mut Vec<u8>, name: &str, mode: u32, contents: &[u8]) {
+    let mut fields = [0u32; 13];
+    fields[1] = mode;
+    fields[6] = contents.len() as u32;
+    fields[11] = name.len() as u32 + 1;
+
+    archive.extend_from_slice(b"070701");
+    for field in &fields {
+        archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+    }
+    archive.extend_from_slice(name.as_bytes());
+    archive.push(0);
+    let aligned = (archive.len() + 3) & !3;
+    archive.resize(aligned, 0);
+    archive.extend_from_slice(contents);
+    let aligned = (archive.len() + 3) & !3;
+    archive.resize(aligned, 0);
+}
+
+/// Returns an archive with a directory and an executable, like a ramdisk.
+fn sample_archive() -> Vec<u8> {
+    let mut archive = Vec::new();
+    push_entry(&mut archive, "sbin", 0o40755, b"");
+    push_entry(&mut archive, "init", 0o100750, b"#!/bin/sh\n");
+    push_entry(&mut archive, "TRAILER!!!", 0, b"");
+    archive
+}
+
+#[test]
+fn list_entries() {
+    let entries = parse_cpio(&sample_archive()).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            CpioEntry {
+                name: "sbin".to_owned(),
+                mode: 0o40755,
+                size: 0,
+            },
+            CpioEntry {
+                name: "init".to_owned(),
+                mode: 0o100750,
+                size: 10,
+            },
+        ]
+    );
+}
+
+#[test]
+fn reject_invalid_archives() {
+    let archive = sample_archive();
+    match parse_cpio(&archive[..150]) {
+        Err(CpioError::Truncated) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let mut bad_magic = archive.clone();
+    bad_magic[0] = b'1';
+    match parse_cpio(&bad_magic) {
+        Err(CpioError::BadMagic(0)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(feature = "decompress")]
+#[test]
+fn ramdisk_file_list() {
+    let ramdisk = compress(&sample_archive(), Compression::Gzip).unwrap();
+    let boot_image = BootImageBuilder::new().ramdisk(ramdisk).build().unwrap();
+    let names: Vec<String> = boot_image
+        .ramdisk_file_list()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    assert_eq!(names, vec!["sbin", "init"]);
+}
+
+#[test]
+fn replace_file() {
+    let contents = b"#!/system/bin/sh\nexit\n";
+    let archive = replace_cpio_file(&sample_archive(), "init", contents).unwrap();
+    let entries = parse_cpio(&archive).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].name, "init");
+    assert_eq!(entries[1].size, 22);
+    assert_eq!(entries[1].mode, 0o100750);
+    assert_eq!(&archive[archive.len() - 4..], &[0; 4][..]);
+    assert_eq!(archive.len() % 4, 0);
+
+    match replace_cpio_file(&sample_archive(), "init.rc", b"") {
+        Err(CpioError::FileNotFound(ref path)) if path == "init.rc" => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[cfg(feature = "decompress")]
+#[test]
+fn ramdisk_replace_file() {
+    let ramdisk = compress(&sample_archive(), Compression::Gzip).unwrap();
+    let mut boot_image = BootImageBuilder::new().ramdisk(ramdisk).build().unwrap();
+    boot_image.ramdisk_replace_file("init", b"#!/bin/sh\nexec /sbin/init\n").unwrap();
+    assert_eq!(Compression::detect(boot_image.ramdisk()), Compression::Gzip);
+    assert_eq!(boot_image.header().ramdisk_size as usize, boot_image.ramdisk().len());
+
+    let entries = boot_image.ramdisk_file_list().unwrap();
+    assert_eq!(entries[1].name, "init");
+    assert_eq!(entries[1].size, 26);
+    assert!(boot_image.ramdisk_replace_file("missing", b"").is_err());
+}
@@ -0,0 +1,38 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImageBuilder, DtbIter};
+
+/// Returns a device tree blob of the given size, which only has the magic and
+/// the total size of its header filled in.
+fn fdt(size: u32, fill: u8) -> Vec<u8> {
+    let mut blob = vec![fill; size as usize];
+    blob[..4].copy_from_slice(&[0xd0, 0x0d, 0xfe, 0xed]);
+    blob[4..8].copy_from_slice(&size.to_be_bytes());
+    blob
+}
+
+#[test]
+fn split_concatenated_blobs() {
+    let mut device_tree = fdt(100, 1);
+    device_tree.extend_from_slice(&fdt(60, 2));
+    let blobs: Vec<&[u8]> = DtbIter::new(&device_tree).collect();
+    assert_eq!(blobs.len(), 2);
+    assert_eq!(blobs[0], &fdt(100, 1)[..]);
+    assert_eq!(blobs[1], &fdt(60, 2)[..]);
+
+    let mut boot_image = BootImageBuilder::new().kernel(vec![1; 10]).build().unwrap();
+    boot_image.insert_device_tree(device_tree);
+    assert_eq!(boot_image.dtbs().map(<[u8]>::len).collect::<Vec<_>>(), vec![100, 60]);
+}
+
+#[test]
+fn skip_truncated_blobs() {
+    let mut device_tree = vec![0; 16];
+    device_tree.extend_from_slice(&fdt(40, 3));
+    // A magic whose total size runs past the end of the section.
+    device_tree.extend_from_slice(&fdt(200, 4)[..50]);
+    let blobs: Vec<&[u8]> = DtbIter::new(&device_tree).collect();
+    assert_eq!(blobs, vec![&fdt(40, 3)[..]]);
+
+    assert_eq!(DtbIter::new(&[]).count(), 0);
+}
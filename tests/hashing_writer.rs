@@ -0,0 +1,53 @@
+extern crate android_bootimage;
+
+use android_bootimage::{HEADER_SIZE, HashingWriter, Header};
+use std::hash::Hasher;
+use std::io::Write;
+
+/// A 64 bit FNV-1a hasher, which hashes the same bytes equally regardless of
+/// how they are split up.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn fnv(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv(0xcbf29ce484222325);
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+#[test]
+fn tees_written_bytes() {
+    let mut writer = HashingWriter::new(Vec::new(), Fnv(0xcbf29ce484222325));
+    let header = Header::default();
+    assert_eq!(header.write_to(&mut writer).unwrap(), HEADER_SIZE);
+    writer.write_all(b"kernel").unwrap();
+
+    let mut expected = Vec::new();
+    header.write_to(&mut expected).unwrap();
+    expected.extend_from_slice(b"kernel");
+
+    assert_eq!(writer.writer(), &expected);
+    assert_eq!(Hasher::finish(&writer), fnv(&expected));
+    let (output, hasher) = writer.into_inner();
+    assert_eq!(output, expected);
+    assert_eq!(hasher.finish(), fnv(&expected));
+}
+
+#[test]
+fn hasher_writes_are_not_written() {
+    let mut writer = HashingWriter::new(Vec::new(), Fnv(0xcbf29ce484222325));
+    Hasher::write(&mut writer, b"hashed only");
+    assert!(writer.writer().is_empty());
+    assert_eq!(writer.hasher().finish(), fnv(b"hashed only"));
+}
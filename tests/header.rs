@@ -0,0 +1,275 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BadHeaderError, Endianness, HEADER_SIZE, Header, HeaderFieldError,
+                        LogicalHeader, ReadBootImageError, SocProfile, UniqueId,
+                        is_valid_page_size};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+
+#[test]
+fn display() {
+    let mut header = Header::default();
+    header.set_product_name("SM-G930F").unwrap();
+    header.page_size = 4096;
+    let output = header.to_string();
+    assert!(output.contains("Magic:                 valid"));
+    assert!(output.contains("Page size:             4096"));
+    assert!(output.contains("Product name:          SM-G930F"));
+    assert!(output.contains(&format!("0x{:08X}", header.kernel_load_address)));
+}
+
+#[test]
+fn soc_defaults() {
+    let mut header = Header::default();
+    header.apply_defaults_for(SocProfile::Msm);
+    assert_eq!(header.kernel_load_address, 0x80008000);
+    assert_eq!(header.ramdisk_load_address, 0x81000000);
+    assert_eq!(header.second_load_address, 0x80f00000);
+    assert_eq!(header.kernel_tags_address, 0x80000100);
+
+    header.apply_defaults_for(SocProfile::Exynos);
+    assert_eq!(header.kernel_load_address, 0x10008000);
+    assert_eq!(header.ramdisk_load_address, 0x11000000);
+    assert_eq!(header.second_load_address, 0x100f0000);
+    assert_eq!(header.kernel_tags_address, 0x10000100);
+}
+
+#[test]
+fn set_load_addresses() {
+    let mut header = Header::default();
+    header.set_kernel_load_address(1);
+    header.set_ramdisk_load_address(2);
+    header.set_second_load_address(3);
+    header.set_kernel_tags_address(4);
+    assert_eq!(
+        (
+            header.kernel_load_address,
+            header.ramdisk_load_address,
+            header.second_load_address,
+            header.kernel_tags_address,
+        ),
+        (1, 2, 3, 4)
+    );
+}
+
+#[test]
+fn cmdline_round_trip() {
+    let cmdline = "console=ttyHSL0,115200,n8 androidboot.hardware=qcom user_debug=31 -v";
+    let mut header = Header::default();
+    header.set_cmdline(cmdline).unwrap();
+    assert_eq!(header.cmdline(), cmdline);
+
+    let mut bytes = [0; HEADER_SIZE];
+    header.write_to(&mut &mut bytes[..]).unwrap();
+    assert_eq!(Header::parse(&bytes).cmdline(), cmdline);
+}
+
+#[test]
+fn cmdline_limit() {
+    let mut header = Header::default();
+    assert!(header.set_cmdline(&"x".repeat(511)).is_ok());
+    match header.set_cmdline(&"x".repeat(512)) {
+        Err(HeaderFieldError::CmdlineTooLong(512)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(header.cmdline().len(), 511);
+}
+
+#[test]
+fn endianness() {
+    let mut header = Header::default();
+    header.kernel_size = 0x01020304;
+
+    let mut bytes = [0; HEADER_SIZE];
+    header.write_to_endian(&mut &mut bytes[..], Endianness::Big).unwrap();
+    assert_eq!(&bytes[8..12], &[1, 2, 3, 4]);
+    assert_eq!(Header::parse_endian(&bytes, Endianness::Big), header);
+    assert_eq!(Header::parse(&bytes).kernel_size, 0x04030201);
+}
+
+#[test]
+fn entry_point_and_ramdisk_region() {
+    let mut header = Header::default();
+    header.kernel_load_address = 0x80008000;
+    header.ramdisk_load_address = 0x81000000;
+    header.ramdisk_size = 0x1234;
+    assert_eq!(header.entry_point(), 0x80008000);
+    assert_eq!(header.ramdisk_region(), (0x81000000, 0x1234));
+}
+
+#[test]
+fn valid_page_sizes() {
+    for &page_size in &[2048, 4096, 8192, 16384] {
+        assert!(is_valid_page_size(page_size));
+    }
+    for &page_size in &[0, 1024, 3000, 6144, 32768] {
+        assert!(!is_valid_page_size(page_size));
+    }
+
+    let mut header = Header::default();
+    assert!(header.has_valid_page_size());
+    header.page_size = 3000;
+    assert!(!header.has_valid_page_size());
+}
+
+#[test]
+fn try_from_slice() {
+    let mut header = Header::default();
+    header.kernel_size = 1234;
+    let mut bytes = vec![0; HEADER_SIZE];
+    header.write_to(&mut &mut bytes[..]).unwrap();
+    assert_eq!(Header::try_from(&bytes[..]).unwrap(), header);
+
+    bytes.extend_from_slice(&[0xFF; 100]);
+    assert_eq!(Header::try_from(&bytes[..]).unwrap(), header);
+
+    match Header::try_from(&bytes[..HEADER_SIZE - 1]) {
+        Err(ReadBootImageError::Io(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    bytes[0] = b'X';
+    match Header::try_from(&bytes[..]) {
+        Err(ReadBootImageError::BadHeader(BadHeaderError::BadMagic(_))) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn unique_id_hex() {
+    let mut id = [0; 32];
+    id[0] = 0x01;
+    id[1] = 0xAB;
+    id[31] = 0xFF;
+    let mut header = Header::default();
+    header.unique_id = UniqueId(id);
+    assert_eq!(
+        header.unique_id_hex(),
+        "01ab0000000000000000000000000000000000000000000000000000000000ff"
+    );
+}
+
+#[test]
+fn header_template() {
+    let mut header = Header::default();
+    header.magic = *b"OEMBOOT!";
+    header.kernel_size = 1;
+    header.ramdisk_size = 2;
+    header.second_size = 3;
+    header.device_tree_size = 4;
+    header.kernel_load_address = 0x80008000;
+    header.page_size = 4096;
+    header.unique_id = UniqueId([1; 32]);
+    header.set_cmdline("quiet").unwrap();
+
+    let template = header.header_template();
+    assert!(template.has_correct_magic());
+    assert_eq!(
+        (template.kernel_size, template.ramdisk_size, template.second_size),
+        (0, 0, 0)
+    );
+    assert_eq!(template.device_tree_size, 0);
+    assert_eq!(template.unique_id, UniqueId::default());
+    assert_eq!(template.kernel_load_address, 0x80008000);
+    assert_eq!(template.page_size, 4096);
+    assert_eq!(template.cmdline(), "quiet");
+
+    header.reset_sizes();
+    assert_eq!(
+        (header.kernel_size, header.ramdisk_size, header.second_size, header.device_tree_size),
+        (0, 0, 0, 0)
+    );
+    assert_eq!(header.page_size, 4096);
+}
+
+#[test]
+fn same_product_ignores_padding() {
+    let mut header = Header::default();
+    header.set_product_name("hero").unwrap();
+    header.set_cmdline("quiet").unwrap();
+
+    // Fill the padding after the terminators of the product name, which starts
+    // at offset 48, and the command line that follows it.
+    let mut bytes = [0; HEADER_SIZE];
+    header.write_to(&mut &mut bytes[..]).unwrap();
+    for byte in &mut bytes[48 + 5..48 + 24] {
+        *byte = 0xAA;
+    }
+    for byte in &mut bytes[72 + 6..72 + 512] {
+        *byte = 0xAA;
+    }
+    let padded = Header::parse(&bytes);
+    assert!(padded != header);
+    assert!(padded.same_product(&header));
+    assert!(padded.same_cmdline(&header));
+
+    let mut other = Header::default();
+    other.set_product_name("herolte").unwrap();
+    assert!(!other.same_product(&header));
+}
+
+fn hash_of<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[test]
+fn hash_ignores_id_and_reserved_field() {
+    let header = Header::default();
+    let mut other = Header::default();
+    other.unique_id = UniqueId([1; 32]);
+    other.set_reserved_raw(0x1234);
+    assert!(header != other);
+    assert!(header.logical_eq(&other));
+    assert_eq!(hash_of(&header), hash_of(&other));
+
+    let mut different = Header::default();
+    different.kernel_size = 1;
+    assert!(!header.logical_eq(&different));
+
+    let headers = vec![header.clone(), other.clone(), different.clone()];
+    let set: HashSet<LogicalHeader> = headers.into_iter().map(LogicalHeader).collect();
+    assert_eq!(set.len(), 2);
+
+    let mut map = HashMap::new();
+    map.insert(LogicalHeader(header), "first");
+    map.insert(LogicalHeader(other.clone()), "second");
+    assert_eq!(map.len(), 1);
+    assert_eq!(map[&LogicalHeader(other)], "second");
+    assert!(!map.contains_key(&LogicalHeader(different)));
+}
+
+#[test]
+fn mkbootimg_args() {
+    let mut header = Header::default();
+    header.page_size = 4096;
+    header.set_cmdline("console=ttyS0 quiet").unwrap();
+    let args = header.to_mkbootimg_args();
+    let value = |name: &str| {
+        let index = args.iter().position(|arg| arg == name).unwrap();
+        args[index + 1].clone()
+    };
+    assert_eq!(value("--pagesize"), "4096");
+    assert_eq!(value("--cmdline"), "console=ttyS0 quiet");
+    assert_eq!(value("--base"), "0x10000000");
+    assert_eq!(value("--kernel_offset"), "0x00008000");
+    assert_eq!(value("--ramdisk_offset"), "0x01000000");
+    assert_eq!(value("--tags_offset"), "0x00000100");
+    assert!(!args.iter().any(|arg| arg == "--os_version"));
+}
+
+#[test]
+fn unique_id_formatting() {
+    let mut id = UniqueId::default();
+    id[0] = 0x12;
+    id[1] = 0xCD;
+    id[31] = 0x0E;
+    let zeros = "0".repeat(58);
+    assert_eq!(format!("{:x}", id), format!("12cd{}0e", zeros));
+    assert_eq!(format!("{:X}", id), format!("12CD{}0E", zeros));
+    assert_eq!(id.len(), 32);
+    assert_eq!(&id[..2], &[0x12, 0xCD]);
+}
@@ -0,0 +1,1134 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BadHeaderError, BootImage, BootImageBuilder, BorrowedBootImage,
+                        Endianness, ErrorKind, Header, Progress, ReadBootImageError, ReadOptions,
+                        SectionKind, SectionOrder, ValidationIssue, WriteOptions,
+                        WriteSectionError, WriteVerifiedError, seek_to_section};
+use std::collections::HashSet;
+use std::io::{Cursor, ErrorKind as IoErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+fn sample_image() -> BootImage {
+    BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .ramdisk(vec![2; 100])
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn equality() {
+    let boot_image = sample_image();
+    assert!(boot_image == sample_image());
+
+    let mut other = sample_image();
+    other.insert_ramdisk(vec![3; 100]);
+    assert!(boot_image != other);
+    assert!(!boot_image.sections_eq(&other));
+}
+
+#[test]
+fn sections_eq_ignores_header() {
+    let boot_image = sample_image();
+    let mut other = sample_image();
+    other.header_mut().kernel_size = 1;
+    other.header_mut().set_cmdline("quiet").unwrap();
+    assert!(boot_image != other);
+    assert!(boot_image.sections_eq(&other));
+}
+
+#[test]
+fn fingerprint() {
+    let boot_image = sample_image();
+    assert_eq!(boot_image.fingerprint(), sample_image().fingerprint());
+
+    let mut other = sample_image();
+    let mut ramdisk = vec![2; 100];
+    ramdisk[0] = 0;
+    other.insert_ramdisk(ramdisk);
+    assert_ne!(boot_image.fingerprint(), other.fingerprint());
+
+    let set: HashSet<BootImage> = vec![sample_image(), sample_image(), other].into_iter().collect();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn fingerprint_ignores_stale_sizes() {
+    let mut boot_image = sample_image();
+    boot_image.header_mut().kernel_size = 1;
+    assert_eq!(boot_image.fingerprint(), sample_image().fingerprint());
+}
+
+#[test]
+fn bytes_round_trip() {
+    let boot_image = sample_image();
+    let bytes = boot_image.to_bytes();
+    assert_eq!(bytes.len(), 4 * 2048);
+    assert!(BootImage::from_bytes(&bytes, None).unwrap() == boot_image);
+    assert!(BootImage::from_bytes(&bytes[..100], None).is_err());
+}
+
+#[test]
+fn sections() {
+    let boot_image = sample_image();
+    let sections: Vec<_> = boot_image.sections().collect();
+    assert_eq!(
+        sections,
+        vec![
+            (SectionKind::Kernel, &[1; 3000][..]),
+            (SectionKind::Ramdisk, &[2; 100][..]),
+        ]
+    );
+}
+
+#[test]
+fn set_sections_from_slices() {
+    let mut boot_image = sample_image();
+    let data = [7; 10];
+    boot_image.set_kernel(&data);
+    boot_image.set_ramdisk(&data[..5]);
+    boot_image.set_second_ramdisk(&data[..3]);
+    boot_image.set_device_tree(&data[..1]);
+    assert_eq!(boot_image.kernel(), &data[..]);
+    assert_eq!(boot_image.ramdisk(), &data[..5]);
+    assert_eq!(boot_image.second_ramdisk(), &data[..3]);
+    assert_eq!(boot_image.device_tree(), &data[..1]);
+
+    let header = boot_image.header();
+    assert_eq!(
+        (header.kernel_size, header.ramdisk_size, header.second_size, header.device_tree_size),
+        (10, 5, 3, 1)
+    );
+}
+
+#[test]
+fn reserved_field_survives_round_trip() {
+    let mut boot_image = sample_image();
+    assert_eq!(boot_image.header().reserved_raw(), 0x02000000);
+    boot_image.header_mut().set_reserved_raw(0x1234);
+
+    let read = BootImage::from_bytes(&boot_image.to_bytes(), None).unwrap();
+    assert_eq!(read.header().reserved_raw(), 0x1234);
+    assert_eq!(read.to_bytes(), boot_image.to_bytes());
+}
+
+#[test]
+fn crc32() {
+    let boot_image = BootImageBuilder::new().kernel(b"1234".to_vec()).build().unwrap();
+    assert_eq!(boot_image.crc32(), 0x9BE3E0A3);
+
+    // The sections are checksummed as if they were concatenated.
+    let boot_image = BootImageBuilder::new()
+        .kernel(b"1234".to_vec())
+        .ramdisk(b"56789".to_vec())
+        .build()
+        .unwrap();
+    assert_eq!(boot_image.crc32(), 0xCBF43926);
+}
+
+#[test]
+fn big_endian_round_trip() {
+    let boot_image = sample_image();
+    let write_options = WriteOptions {
+        endianness: Endianness::Big,
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    boot_image.write_to_opts(&mut bytes, &write_options).unwrap();
+    assert_eq!(&bytes[8..12], &3000u32.to_be_bytes());
+    assert!(BootImage::from_bytes(&bytes, None).is_err());
+
+    let read_options = ReadOptions {
+        endianness: Endianness::Big,
+        ..ReadOptions::default()
+    };
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &read_options).unwrap();
+    assert!(read == boot_image);
+
+    let (read, issues) =
+        BootImage::read_best_effort_opts(&mut Cursor::new(&bytes), None, &read_options);
+    assert!(issues.is_empty());
+    assert!(read.sections_eq(&boot_image));
+
+    let borrowed = BorrowedBootImage::from_slice_endian(&bytes, None, Endianness::Big).unwrap();
+    assert_eq!(borrowed.kernel(), boot_image.kernel());
+}
+
+#[test]
+fn debug_output_summarizes_sections() {
+    let boot_image = BootImageBuilder::new().kernel(vec![1; 12345]).build().unwrap();
+    let output = format!("{:?}", boot_image);
+    assert!(output.contains("kernel: 12345 bytes"));
+    assert!(output.contains("ramdisk: 0 bytes"));
+    assert!(output.len() < 1000);
+}
+
+/// A source that counts the bytes read from it.
+struct CountingSource<R> {
+    source: R,
+    bytes_read: usize,
+}
+
+impl<R: Read> Read for CountingSource<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        let bytes_read = self.source.read(buffer)?;
+        self.bytes_read += bytes_read;
+        Ok(bytes_read)
+    }
+}
+
+impl<R: Seek> Seek for CountingSource<R> {
+    fn seek(&mut self, position: SeekFrom) -> IoResult<u64> {
+        self.source.seek(position)
+    }
+}
+
+#[test]
+fn read_section_range() {
+    let mut ramdisk = vec![0; 100_000];
+    ramdisk[..8].copy_from_slice(b"07070100");
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .ramdisk(ramdisk)
+        .build()
+        .unwrap();
+    let bytes = boot_image.to_bytes();
+
+    let mut source = CountingSource {
+        source: Cursor::new(&bytes),
+        bytes_read: 0,
+    };
+    let range =
+        BootImage::read_section_range(&mut source, SectionKind::Ramdisk, 0, 8, None).unwrap();
+    assert_eq!(range, b"07070100");
+    assert!(source.bytes_read < 2048);
+
+    let kernel = SectionKind::Kernel;
+    let range = BootImage::read_section_range(&mut Cursor::new(&bytes), kernel, 2990, 10, None);
+    assert_eq!(range.unwrap(), vec![1; 10]);
+    let range = BootImage::read_section_range(&mut Cursor::new(&bytes), kernel, 2991, 10, None);
+    assert!(range.is_err());
+}
+
+#[test]
+fn write_sections_concatenated() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 50]);
+
+    let mut output = Vec::new();
+    let sections = [SectionKind::Kernel, SectionKind::DeviceTree];
+    let bytes_written = boot_image.write_sections_concatenated(&sections, &mut output).unwrap();
+    assert_eq!(bytes_written, 3050);
+    assert_eq!(output.len(), 3050);
+    assert_eq!(&output[..3000], boot_image.kernel());
+    assert_eq!(&output[3000..], boot_image.device_tree());
+}
+
+#[test]
+fn read_best_effort_truncated_ramdisk() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 50]);
+    let bytes = boot_image.to_bytes();
+
+    let truncated = &bytes[..3 * 2048 + 40];
+    let (read, issues) = BootImage::read_best_effort(&mut Cursor::new(truncated), None);
+    assert_eq!(read.kernel(), boot_image.kernel());
+    assert_eq!(read.ramdisk(), &boot_image.ramdisk()[..40]);
+    assert!(read.device_tree().is_empty());
+    assert_eq!(
+        issues,
+        vec![
+            ValidationIssue::TruncatedSection {
+                section: SectionKind::Ramdisk,
+                expected: 100,
+                got: 40,
+            },
+            ValidationIssue::TruncatedSection {
+                section: SectionKind::DeviceTree,
+                expected: 50,
+                got: 0,
+            },
+        ]
+    );
+
+    let (_, issues) = BootImage::read_best_effort(&mut Cursor::new(&bytes[..100]), None);
+    assert_eq!(issues, vec![ValidationIssue::UnreadableHeader]);
+}
+
+/// Returns the serialized boot image, with the page size in its header set to
+/// the given value.
+fn with_page_size_field(boot_image: &BootImage, page_size: u32) -> Vec<u8> {
+    let mut bytes = boot_image.to_bytes();
+    let mut header = boot_image.header().clone();
+    header.page_size = page_size;
+    header.write_to(&mut &mut bytes[..]).unwrap();
+    bytes
+}
+
+#[test]
+fn infer_page_size() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .ramdisk(vec![2; 100])
+        .page_size(4096)
+        .build()
+        .unwrap();
+    let bytes = with_page_size_field(&boot_image, 0);
+    assert_eq!(BootImage::infer_page_size(&mut Cursor::new(&bytes)), Some(4096));
+
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.page_size(), 4096);
+    assert!(read.sections_eq(&boot_image));
+
+    assert_eq!(BootImage::infer_page_size(&mut Cursor::new(&bytes[..5000])), None);
+
+    let write_options = WriteOptions {
+        endianness: Endianness::Big,
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    boot_image.write_to_opts(&mut bytes, &write_options).unwrap();
+    // The page size is the 12th field of the header.
+    bytes[44..48].copy_from_slice(&[0; 4]);
+    let big_endian = Endianness::Big;
+    let inferred = BootImage::infer_page_size_endian(&mut Cursor::new(&bytes), big_endian);
+    assert_eq!(inferred, Some(4096));
+    let read_options = ReadOptions {
+        endianness: Endianness::Big,
+        ..ReadOptions::default()
+    };
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &read_options).unwrap();
+    assert_eq!(read.page_size(), 4096);
+    assert!(read.sections_eq(&boot_image));
+}
+
+#[test]
+fn size_drift() {
+    let mut boot_image = sample_image();
+    assert!(boot_image.size_drift().is_empty());
+
+    boot_image.header_mut().kernel_size = 10;
+    boot_image.header_mut().device_tree_size = 20;
+    assert_eq!(
+        boot_image.size_drift(),
+        vec![(SectionKind::Kernel, 10, 3000), (SectionKind::DeviceTree, 20, 0)]
+    );
+    assert_eq!(boot_image.header().kernel_size, 10);
+
+    boot_image.update_all_sizes();
+    assert!(boot_image.size_drift().is_empty());
+    assert!(boot_image == sample_image());
+}
+
+#[test]
+fn read_all() {
+    let first = sample_image();
+    let second = BootImageBuilder::new()
+        .kernel(vec![5; 10])
+        .page_size(4096)
+        .build()
+        .unwrap();
+
+    let mut bytes = first.to_bytes();
+    bytes.extend_from_slice(&[0; 2048]);
+    bytes.extend_from_slice(&second.to_bytes());
+    let boot_images = BootImage::read_all(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(boot_images.len(), 2);
+    assert!(boot_images[0] == first);
+    assert!(boot_images[1] == second);
+
+    // Reading stops at data that is not a boot image.
+    bytes.extend_from_slice(b"garbage");
+    assert_eq!(BootImage::read_all(&mut Cursor::new(&bytes)).unwrap().len(), 2);
+}
+
+#[test]
+fn read_all_infers_page_size_of_last_image() {
+    let mut bytes = sample_image().to_bytes();
+    bytes.extend_from_slice(&with_page_size_field(&sample_image(), 0));
+    let boot_images = BootImage::read_all(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(boot_images.len(), 2);
+    assert_eq!(boot_images[1].page_size(), 2048);
+
+    let mut bytes = with_page_size_field(&sample_image(), 0);
+    bytes.extend_from_slice(&sample_image().to_bytes());
+    assert!(BootImage::read_all(&mut Cursor::new(&bytes)).is_err());
+}
+
+#[test]
+fn custom_magic() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .magic(*b"OEMBOOT!")
+        .build()
+        .unwrap();
+    assert_eq!(boot_image.header().magic_bytes(), b"OEMBOOT!");
+    let bytes = boot_image.to_bytes();
+
+    assert!(BootImage::from_bytes(&bytes, None).is_err());
+    let read =
+        BootImage::read_from_with_magic(&mut Cursor::new(&bytes), None, b"OEMBOOT!").unwrap();
+    assert!(read == boot_image);
+    assert!(read.header().has_magic(b"OEMBOOT!"));
+    assert!(!read.header().has_correct_magic());
+}
+
+// A zeroed allocation is only backed by memory once it is written to, so this
+// does not actually use 4 GiB.
+#[cfg(target_pointer_width = "64")]
+#[test]
+#[should_panic(expected = "larger than 4 GiB")]
+fn oversized_section() {
+    let mut boot_image = sample_image();
+    boot_image.insert_ramdisk(vec![0; u32::MAX as usize + 1]);
+}
+
+#[test]
+fn merge_addressing_from() {
+    let mut other = Header::default();
+    other.kernel_load_address = 0x80008000;
+    other.ramdisk_load_address = 0x81000000;
+    other.second_load_address = 0x80f00000;
+    other.kernel_tags_address = 0x80000100;
+    other.page_size = 4096;
+    other.kernel_size = 1;
+    other.set_cmdline("console=ttyS0").unwrap();
+    other.set_product_name("other").unwrap();
+
+    let mut boot_image = sample_image();
+    boot_image.merge_addressing_from(&other);
+    let header = boot_image.header();
+    assert_eq!(header.kernel_load_address, 0x80008000);
+    assert_eq!(header.ramdisk_load_address, 0x81000000);
+    assert_eq!(header.second_load_address, 0x80f00000);
+    assert_eq!(header.kernel_tags_address, 0x80000100);
+    assert_eq!(header.page_size, 4096);
+    assert_eq!(header.cmdline(), "console=ttyS0");
+    assert_eq!(header.product_name_str(), "other");
+    assert_eq!(header.kernel_size, 3000);
+    assert_eq!(header.ramdisk_size, 100);
+
+    // A page size of 0 is not copied.
+    other.page_size = 0;
+    boot_image.merge_addressing_from(&other);
+    assert_eq!(boot_image.page_size(), 4096);
+}
+
+#[test]
+fn write_section_by_name() {
+    let mut boot_image = sample_image();
+    boot_image.insert_second_ramdisk(vec![3; 30]);
+    boot_image.insert_device_tree(vec![4; 40]);
+
+    for &(name, size) in &[
+        ("header", 616),
+        ("kernel", 3000),
+        ("ramdisk", 100),
+        ("second", 30),
+        ("dt", 40),
+    ] {
+        let mut output = Vec::new();
+        assert_eq!(boot_image.write_section_to(name, &mut output).unwrap(), size);
+        assert_eq!(output.len(), size);
+    }
+
+    let mut output = Vec::new();
+    let error = boot_image.write_section_to("bootloader", &mut output).unwrap_err();
+    assert_eq!(error.kind(), IoErrorKind::InvalidInput);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn read_options_limit_section_size() {
+    let bytes = sample_image().to_bytes();
+    let options = ReadOptions {
+        max_section_bytes: 1000,
+        ..ReadOptions::default()
+    };
+    let error = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &options).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::SectionTooLarge);
+
+    let options = ReadOptions {
+        max_section_bytes: 3000,
+        prealloc: false,
+        ..ReadOptions::default()
+    };
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &options).unwrap();
+    assert!(read == sample_image());
+}
+
+#[test]
+fn trailing_bytes_survive_round_trip() {
+    let mut bytes = sample_image().to_bytes();
+    let image_size = bytes.len();
+    bytes.extend((0..256).map(|byte| byte as u8));
+
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.trailing_bytes().len(), 256);
+    assert_eq!(read.to_bytes(), bytes);
+    assert_ne!(read.fingerprint(), sample_image().fingerprint());
+
+    let options = ReadOptions {
+        read_trailing_bytes: false,
+        ..ReadOptions::default()
+    };
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &options).unwrap();
+    assert!(read.trailing_bytes().is_empty());
+    assert_eq!(read.to_bytes().len(), image_size);
+}
+
+#[test]
+fn swapped_section_order() {
+    let order = SectionOrder::new([
+        SectionKind::Kernel,
+        SectionKind::Ramdisk,
+        SectionKind::DeviceTree,
+        SectionKind::Second,
+    ]).unwrap();
+    let mut boot_image = sample_image();
+    boot_image.insert_second_ramdisk(vec![3; 30]);
+    boot_image.insert_device_tree(vec![4; 40]);
+    let standard_fingerprint = boot_image.fingerprint();
+    boot_image.set_section_order(order);
+    assert_ne!(boot_image.fingerprint(), standard_fingerprint);
+    assert_eq!(boot_image.device_tree_offset(), 4 * 2048);
+    assert_eq!(boot_image.second_ramdisk_offset(), 5 * 2048);
+
+    let bytes = boot_image.to_bytes();
+    assert_eq!(&bytes[4 * 2048..4 * 2048 + 40], boot_image.device_tree());
+    assert_eq!(&bytes[5 * 2048..5 * 2048 + 30], boot_image.second_ramdisk());
+
+    let options = ReadOptions {
+        section_order: order,
+        ..ReadOptions::default()
+    };
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &options).unwrap();
+    assert!(read == boot_image);
+    assert_eq!(read.section_order(), order);
+
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.second_ramdisk(), &[4; 30][..]);
+}
+
+#[test]
+fn section_order_requires_every_section() {
+    assert!(SectionOrder::new([
+        SectionKind::Kernel,
+        SectionKind::Kernel,
+        SectionKind::Second,
+        SectionKind::DeviceTree,
+    ]).is_none());
+    assert_eq!(SectionOrder::default(), SectionOrder::STANDARD);
+}
+
+#[test]
+fn truncated_kernel() {
+    let bytes = sample_image().to_bytes();
+    match BootImage::from_bytes(&bytes[..2048 + 1000], None) {
+        Err(ReadBootImageError::UnexpectedEof {
+            section: SectionKind::Kernel,
+            expected: 3000,
+            got: 1000,
+        }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn roundtrip_safety() {
+    let boot_image = sample_image();
+    assert!(boot_image.validate().is_empty());
+    assert!(boot_image.is_roundtrip_safe());
+
+    let mut desynced = sample_image();
+    desynced.header_mut().ramdisk_size = 1;
+    assert!(!desynced.is_roundtrip_safe());
+    assert_eq!(
+        desynced.validate(),
+        vec![ValidationIssue::SizeMismatch {
+            section: SectionKind::Ramdisk,
+            header_size: 1,
+            actual_size: 100,
+        }]
+    );
+}
+
+#[test]
+fn roundtrip_safety_with_custom_magic() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .magic(*b"OEMBOOT!")
+        .build()
+        .unwrap();
+    assert_eq!(boot_image.validate(), vec![ValidationIssue::BadMagic]);
+    assert!(!boot_image.is_roundtrip_safe());
+    assert!(boot_image.validate_with_magic(b"OEMBOOT!").is_empty());
+    assert!(boot_image.is_roundtrip_safe_with_magic(b"OEMBOOT!"));
+}
+
+#[test]
+fn sections_summary() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 40]);
+    assert_eq!(
+        boot_image.sections_summary(),
+        [
+            (SectionKind::Header, 0, 616),
+            (SectionKind::Kernel, 2048, 3000),
+            (SectionKind::Ramdisk, 3 * 2048, 100),
+            (SectionKind::Second, 4 * 2048, 0),
+            (SectionKind::DeviceTree, 4 * 2048, 40),
+        ]
+    );
+    for &(section, offset, size) in &boot_image.sections_summary()[1..] {
+        let data = boot_image.sections().find(|&(kind, _)| kind == section);
+        assert_eq!(data.map_or(0, |(_, data)| data.len()), size);
+        let mut bytes = Vec::new();
+        boot_image.write_section_to(section.name(), &mut bytes).unwrap();
+        assert_eq!(&boot_image.to_bytes()[offset..offset + size], &bytes[..]);
+    }
+}
+
+#[test]
+fn pad_byte() {
+    let boot_image = sample_image();
+    let options = WriteOptions {
+        pad_byte: 0xFF,
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    assert_eq!(boot_image.write_to_opts(&mut bytes, &options).unwrap(), 4 * 2048);
+    assert_eq!(bytes.len(), 4 * 2048);
+    assert!(bytes[616..2048].iter().all(|&byte| byte == 0xFF));
+    assert!(bytes[2048 + 3000..3 * 2048].iter().all(|&byte| byte == 0xFF));
+    assert!(bytes[3 * 2048 + 100..].iter().all(|&byte| byte == 0xFF));
+
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert!(read == boot_image);
+}
+
+#[test]
+fn read_header_only() {
+    let boot_image = sample_image();
+    let bytes = boot_image.to_bytes();
+    let mut source = Cursor::new(&bytes);
+    let header = BootImage::read_header_only(&mut source, None).unwrap();
+    assert_eq!(&header, boot_image.header());
+    assert_eq!(source.position(), 616);
+
+    let bytes = with_page_size_field(&boot_image, 0);
+    let error = BootImage::read_header_only(&mut Cursor::new(&bytes), None).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NoPageSize);
+    let header = BootImage::read_header_only(&mut Cursor::new(&bytes), Some(4096)).unwrap();
+    assert_eq!(header.page_size, 4096);
+}
+
+#[test]
+fn error_kinds() {
+    let bytes = sample_image().to_bytes();
+    let kind = |bytes: &[u8]| BootImage::from_bytes(bytes, None).unwrap_err().kind();
+    assert_eq!(kind(&bytes[..100]), ErrorKind::UnexpectedEof);
+    assert_eq!(kind(&bytes[..3000]), ErrorKind::UnexpectedEof);
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    assert_eq!(kind(&bad_magic), ErrorKind::BadMagic);
+
+    let mut boot_image = sample_image();
+    let mut header = Header::default();
+    header.page_size = 0;
+    assert_eq!(boot_image.insert_header(header).unwrap_err().kind(), ErrorKind::NoPageSize);
+    let error = BootImageBuilder::new().product_name(&"x".repeat(30)).build().unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::ProductNameTooLong);
+}
+
+#[test]
+fn infer_page_size_keeps_position() {
+    let bytes = with_page_size_field(&sample_image(), 0);
+    let mut source = Cursor::new(&bytes);
+    source.seek(SeekFrom::Start(100)).unwrap();
+    assert_eq!(BootImage::infer_page_size(&mut source), Some(2048));
+    assert_eq!(source.position(), 100);
+
+    source.seek(SeekFrom::End(0)).unwrap();
+    assert_eq!(BootImage::infer_page_size(&mut source), Some(2048));
+    assert_eq!(source.position(), bytes.len() as u64);
+}
+
+#[test]
+fn reader_in_small_chunks() {
+    let mut bytes = sample_image().to_bytes();
+    bytes.extend_from_slice(&[3; 10]);
+    let boot_image = BootImage::from_bytes(&bytes, None).unwrap();
+    let mut reader = boot_image.reader();
+    let mut streamed = Vec::new();
+    let mut chunk = [0; 7];
+    loop {
+        match reader.read(&mut chunk).unwrap() {
+            0 => break,
+            length => streamed.extend_from_slice(&chunk[..length]),
+        }
+    }
+    assert_eq!(streamed, boot_image.to_bytes());
+    assert_eq!(streamed, bytes);
+}
+
+#[test]
+fn write_with_update_id() {
+    let boot_image = sample_image();
+    let options = WriteOptions {
+        update_id: true,
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    boot_image.write_to_opts(&mut bytes, &options).unwrap();
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.header().unique_id, boot_image.compute_id());
+    assert_eq!(read.header().unique_id, read.compute_id());
+    assert_ne!(boot_image.header().unique_id, boot_image.compute_id());
+
+    let bytes = boot_image.to_bytes();
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.header().unique_id, boot_image.header().unique_id);
+}
+
+#[test]
+fn clear_sections() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 500]);
+    boot_image.clear_device_tree();
+    assert!(boot_image.device_tree().is_empty());
+    assert_eq!(boot_image.header().device_tree_size, 0);
+
+    boot_image.clear_ramdisk();
+    assert!(boot_image.ramdisk().is_empty());
+    assert_eq!(boot_image.header().ramdisk_size, 0);
+    assert_eq!(boot_image.kernel(), &[1; 3000][..]);
+}
+
+#[test]
+fn write_returns_padded_size() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 10]);
+    let mut bytes = Vec::new();
+    let written = boot_image.write_to(&mut bytes).unwrap();
+    assert_eq!(written, bytes.len());
+    assert_eq!(written, 5 * 2048);
+
+    let mut kernel = Vec::new();
+    assert_eq!(boot_image.write_kernel_to(&mut kernel).unwrap(), kernel.len());
+}
+
+#[test]
+fn samsung_dt_quirk() {
+    let options = ReadOptions {
+        samsung_dt_quirk: true,
+        ..ReadOptions::default()
+    };
+    let read = |bytes: &[u8]| {
+        BootImage::read_from_opts(&mut Cursor::new(bytes), None, &options).unwrap()
+    };
+
+    let boot_image = sample_image();
+    assert_eq!(boot_image.header().dt_size_from_reserved(), None);
+    let read_back = read(&boot_image.to_bytes());
+    assert!(read_back.device_tree().is_empty());
+
+    let mut boot_image = sample_image();
+    boot_image.header_mut().set_reserved_raw(5000);
+    assert_eq!(boot_image.header().dt_size_from_reserved(), Some(5000));
+    let mut bytes = boot_image.to_bytes();
+    let read_back = read(&bytes);
+    assert!(read_back.device_tree().is_empty());
+    assert_eq!(read_back.header().device_tree_size, 0);
+
+    bytes.extend_from_slice(&[4; 5000]);
+    let read_back = read(&bytes);
+    assert_eq!(read_back.device_tree(), &[4; 5000][..]);
+    assert_eq!(read_back.header().device_tree_size, 5000);
+
+    let read_back = BootImage::from_bytes(&bytes, None).unwrap();
+    assert!(read_back.device_tree().is_empty());
+}
+
+type Reports = Arc<Mutex<Vec<(SectionKind, usize)>>>;
+
+/// Returns a progress callback, and the list of reports it collects.
+fn recording_progress() -> (Progress, Reports) {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let collected = reports.clone();
+    let progress = Progress::new(move |section, bytes| {
+        collected.lock().unwrap().push((section, bytes));
+    });
+    (progress, reports)
+}
+
+#[test]
+fn progress_callbacks() {
+    let boot_image = sample_image();
+    let (progress, reports) = recording_progress();
+    let options = WriteOptions {
+        progress: Some(progress),
+        ..WriteOptions::default()
+    };
+    let mut bytes = Vec::new();
+    boot_image.write_to_opts(&mut bytes, &options).unwrap();
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![
+            (SectionKind::Header, 2048),
+            (SectionKind::Kernel, 2 * 2048),
+            (SectionKind::Ramdisk, 2048),
+            (SectionKind::Second, 0),
+            (SectionKind::DeviceTree, 0),
+        ]
+    );
+
+    let (progress, reports) = recording_progress();
+    let options = ReadOptions {
+        progress: Some(progress),
+        ..ReadOptions::default()
+    };
+    BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &options).unwrap();
+    assert_eq!(
+        *reports.lock().unwrap(),
+        vec![
+            (SectionKind::Header, 616),
+            (SectionKind::Kernel, 3000),
+            (SectionKind::Ramdisk, 100),
+            (SectionKind::Second, 0),
+            (SectionKind::DeviceTree, 0),
+        ]
+    );
+}
+
+#[test]
+fn patch_sections() {
+    let mut boot_image = sample_image();
+    boot_image.patch_kernel(|kernel| kernel.extend_from_slice(&[5; 100]));
+    assert_eq!(boot_image.kernel().len(), 3100);
+    assert_eq!(boot_image.header().kernel_size, 3100);
+
+    boot_image.patch_ramdisk(|ramdisk| ramdisk[0] = 9);
+    assert_eq!(boot_image.ramdisk()[0], 9);
+    assert_eq!(boot_image.header().ramdisk_size, 100);
+
+    boot_image.patch_device_tree(|device_tree| device_tree.push(1));
+    assert_eq!(boot_image.header().device_tree_size, 1);
+}
+
+#[test]
+fn with_sections() {
+    let mut header = Header::default();
+    header.set_cmdline("quiet").unwrap();
+    let boot_image =
+        BootImage::with_sections(header, vec![1; 3000], vec![2; 100], vec![3; 10], vec![4; 20])
+            .unwrap();
+    assert_eq!(boot_image.header().kernel_size, 3000);
+    assert_eq!(boot_image.header().second_size, 10);
+    assert_eq!(boot_image.header().device_tree_size, 20);
+
+    let read = BootImage::from_bytes(&boot_image.to_bytes(), None).unwrap();
+    assert!(read == boot_image);
+    assert_eq!(read.header().cmdline(), "quiet");
+
+    let mut header = Header::default();
+    header.page_size = 0;
+    let error = BootImage::with_sections(header, Vec::new(), Vec::new(), Vec::new(), Vec::new())
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::NoPageSize);
+}
+
+#[test]
+fn stream_section() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 10]);
+    let bytes = boot_image.to_bytes();
+    let header = boot_image.header().clone();
+    let mut source = Cursor::new(&bytes);
+
+    seek_to_section(&mut source, &header, SectionKind::Ramdisk).unwrap();
+    assert_eq!(source.position(), 3 * 2048);
+    let mut ramdisk = Vec::new();
+    (&mut source).take(u64::from(header.ramdisk_size)).read_to_end(&mut ramdisk).unwrap();
+    assert_eq!(ramdisk, boot_image.ramdisk());
+
+    seek_to_section(&mut source, &header, SectionKind::DeviceTree).unwrap();
+    let mut device_tree = Vec::new();
+    source.take(u64::from(header.device_tree_size)).read_to_end(&mut device_tree).unwrap();
+    assert_eq!(device_tree, vec![4; 10]);
+
+    let mut header = header;
+    header.page_size = 0;
+    let error = seek_to_section(&mut Cursor::new(&bytes), &header, SectionKind::Kernel);
+    assert_eq!(error.unwrap_err().kind(), IoErrorKind::InvalidInput);
+}
+
+#[test]
+fn normalize() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .product_name("test")
+        .build()
+        .unwrap();
+    let mut bytes = boot_image.to_bytes();
+    // Garbage after the terminator of the product name.
+    bytes[48 + 10] = b'X';
+    let mut other = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(other.header().product_name_str(), "test");
+    assert!(other != boot_image);
+
+    other.header_mut().set_reserved_raw(1);
+    other.header_mut().kernel_size = 1;
+    other.normalize();
+    let mut boot_image = boot_image;
+    boot_image.normalize();
+    assert!(other == boot_image);
+    assert_eq!(other.header().kernel_size, 3000);
+    assert_eq!(other.header().reserved_raw(), 0x02000000);
+}
+
+#[test]
+fn garbage_fails_on_magic() {
+    // A fixed pseudo-random buffer, so the test is reproducible.
+    let mut state = 0x2545F491u32;
+    let garbage: Vec<u8> = (0..2048)
+        .map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        })
+        .collect();
+
+    let mut source = CountingSource {
+        source: Cursor::new(&garbage),
+        bytes_read: 0,
+    };
+    match BootImage::read_from(&mut source, None) {
+        Err(ReadBootImageError::BadHeader(BadHeaderError::BadMagic(_))) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert!(source.bytes_read <= 616);
+}
+
+#[test]
+fn invalid_page_size() {
+    let boot_image = sample_image();
+    let bytes = with_page_size_field(&boot_image, 0xFFFFFFFF);
+    let is_invalid = |result: Result<(), ReadBootImageError>| {
+        matches!(result, Err(ReadBootImageError::InvalidPageSize(0xFFFFFFFF)))
+    };
+
+    let read = BootImage::from_bytes(&bytes, None);
+    assert!(is_invalid(read.map(|_| ())));
+    let read = BootImage::from_bytes(&bytes, Some(2048)).unwrap();
+    assert!(read.sections_eq(&boot_image));
+
+    let header = BootImage::read_header_only(&mut Cursor::new(&bytes), None);
+    assert!(is_invalid(header.map(|_| ())));
+    let ramdisk = SectionKind::Ramdisk;
+    let range = BootImage::read_section_range(&mut Cursor::new(&bytes), ramdisk, 0, 8, None);
+    assert!(is_invalid(range.map(|_| ())));
+    assert!(is_invalid(BorrowedBootImage::from_slice(&bytes, None).map(|_| ())));
+
+    let (read, issues) = BootImage::read_best_effort(&mut Cursor::new(&bytes), None);
+    assert_eq!(issues, vec![ValidationIssue::InvalidPageSize(0xFFFFFFFF)]);
+    assert!(read.kernel().is_empty());
+    let (read, issues) = BootImage::read_best_effort(&mut Cursor::new(&bytes), Some(2048));
+    assert!(issues.is_empty());
+    assert_eq!(read.ramdisk(), boot_image.ramdisk());
+}
+
+#[test]
+fn into_parts() {
+    let mut boot_image = sample_image();
+    boot_image.insert_second_ramdisk(vec![3; 10]);
+    boot_image.insert_device_tree(vec![4; 20]);
+    let header = boot_image.header().clone();
+    let (parts_header, kernel, ramdisk, second, device_tree) = boot_image.into_parts();
+    assert_eq!(parts_header, header);
+    assert_eq!(kernel, vec![1; 3000]);
+    assert_eq!(ramdisk, vec![2; 100]);
+    assert_eq!(second, vec![3; 10]);
+    assert_eq!(device_tree, vec![4; 20]);
+}
+
+#[test]
+fn page_breakdown() {
+    let mut boot_image = sample_image();
+    boot_image.insert_device_tree(vec![4; 2048]);
+    let breakdown = boot_image.page_breakdown();
+    for &(_, size, padded_size) in &breakdown {
+        assert_eq!(padded_size % 2048, 0);
+        assert!(padded_size >= size);
+    }
+    assert_eq!(breakdown[0], (SectionKind::Header, 616, 2048));
+    assert!(breakdown.contains(&(SectionKind::Kernel, 3000, 4096)));
+    assert!(breakdown.contains(&(SectionKind::DeviceTree, 2048, 2048)));
+    let padded_size: usize = breakdown.iter().map(|&(_, _, padded_size)| padded_size).sum();
+    assert_eq!(padded_size, boot_image.total_size());
+}
+
+#[test]
+fn unterminated_strings() {
+    let mut bytes = sample_image().to_bytes();
+    // Fill the product name field up to its last byte.
+    bytes[48..48 + 24].copy_from_slice(&[b'p'; 24]);
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.header().product_name_str(), "p".repeat(24));
+
+    let strict = ReadOptions {
+        strict_strings: true,
+        ..ReadOptions::default()
+    };
+    match BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &strict) {
+        Err(ReadBootImageError::BadHeader(BadHeaderError::ProductNameTooLong(_))) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    let mut bytes = sample_image().to_bytes();
+    bytes[72..72 + 512].copy_from_slice(&[b'c'; 512]);
+    let read = BootImage::from_bytes(&bytes, None).unwrap();
+    assert_eq!(read.header().cmdline(), "c".repeat(512));
+    match BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &strict) {
+        Err(ReadBootImageError::BadHeader(BadHeaderError::CmdlineTooLong(_))) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    let bytes = sample_image().to_bytes();
+    assert!(BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &strict).is_ok());
+}
+
+/// A target that flips the lowest bit of every byte written after the first
+/// page, like faulty storage would.
+struct CorruptingTarget(Cursor<Vec<u8>>);
+
+impl Write for CorruptingTarget {
+    fn write(&mut self, buffer: &[u8]) -> IoResult<usize> {
+        let start = self.0.position() as usize;
+        let corrupted: Vec<u8> = buffer
+            .iter()
+            .enumerate()
+            .map(|(index, &byte)| if start + index >= 2048 { byte ^ 1 } else { byte })
+            .collect();
+        self.0.write(&corrupted)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.0.flush()
+    }
+}
+
+impl Read for CorruptingTarget {
+    fn read(&mut self, buffer: &mut [u8]) -> IoResult<usize> {
+        self.0.read(buffer)
+    }
+}
+
+impl Seek for CorruptingTarget {
+    fn seek(&mut self, position: SeekFrom) -> IoResult<u64> {
+        self.0.seek(position)
+    }
+}
+
+#[test]
+fn write_verified() {
+    let boot_image = sample_image();
+    let mut target = Cursor::new(vec![0xAA; 10]);
+    target.seek(SeekFrom::End(0)).unwrap();
+    let bytes_written = boot_image.write_verified(&mut target).unwrap();
+    assert_eq!(bytes_written, 4 * 2048);
+    assert_eq!(target.position(), 10 + 4 * 2048);
+    assert_eq!(&target.get_ref()[10..], &boot_image.to_bytes()[..]);
+
+    let mut target = CorruptingTarget(Cursor::new(Vec::new()));
+    match boot_image.write_verified(&mut target) {
+        Err(WriteVerifiedError::RoundtripMismatch) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    let mut stale = sample_image();
+    stale.header_mut().kernel_size = 10;
+    assert!(stale.write_verified(&mut Cursor::new(Vec::new())).is_err());
+}
+
+#[test]
+fn unaligned_load_address() {
+    let mut boot_image = sample_image();
+    assert!(boot_image.header().addresses_aligned());
+    assert!(boot_image.validate().is_empty());
+
+    boot_image.header_mut().ramdisk_load_address = 0x11000100;
+    assert!(!boot_image.header().addresses_aligned());
+    assert_eq!(
+        boot_image.validate(),
+        vec![ValidationIssue::UnalignedLoadAddress {
+            section: SectionKind::Ramdisk,
+            address: 0x11000100,
+        }]
+    );
+    assert!(boot_image.is_roundtrip_safe());
+
+    boot_image.header_mut().align_addresses();
+    assert_eq!(boot_image.header().ramdisk_load_address, 0x11000000);
+    assert!(boot_image.validate().is_empty());
+}
+
+#[test]
+fn read_truncated_header() {
+    let mut boot_image = sample_image();
+    boot_image.header_mut().unique_id = boot_image.compute_id();
+    let bytes = boot_image.to_bytes();
+
+    let (header, available) = BootImage::read_header_lenient(&mut &bytes[..600]).unwrap();
+    assert_eq!(available, 600);
+    assert_eq!(header.kernel_size, 3000);
+    assert_eq!(header.ramdisk_size, 100);
+    assert_eq!(header.page_size, 2048);
+    assert_eq!(header.kernel_load_address, boot_image.header().kernel_load_address);
+    assert_eq!(header.unique_id[..16], boot_image.header().unique_id[..16]);
+    assert_eq!(header.unique_id[16..], [0; 16][..]);
+
+    let (header, available) = BootImage::read_header_lenient(&mut &bytes[..]).unwrap();
+    assert_eq!(available, 616);
+    assert_eq!(&header, boot_image.header());
+}
+
+#[test]
+fn write_section_checked() {
+    let mut boot_image = sample_image();
+    let mut bytes = Vec::new();
+    match boot_image.write_section_checked(SectionKind::DeviceTree, &mut bytes) {
+        Err(WriteSectionError::SectionAbsent(SectionKind::DeviceTree)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(bytes.is_empty());
+
+    boot_image.insert_device_tree(vec![4; 50]);
+    let size = boot_image.write_section_checked(SectionKind::DeviceTree, &mut bytes).unwrap();
+    assert_eq!(size, 50);
+    assert_eq!(bytes, vec![4; 50]);
+
+    let mut bytes = Vec::new();
+    let size = boot_image.write_section_checked(SectionKind::Header, &mut bytes).unwrap();
+    assert_eq!(size, bytes.len());
+}
+
+#[test]
+fn force_reads_invalid_headers() {
+    let boot_image = sample_image();
+    let force = ReadOptions {
+        force: true,
+        ..ReadOptions::default()
+    };
+
+    let mut bytes = boot_image.to_bytes();
+    bytes[..8].copy_from_slice(b"NOTANIMG");
+    assert_eq!(BootImage::from_bytes(&bytes, None).unwrap_err().kind(), ErrorKind::BadMagic);
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &force).unwrap();
+    assert_eq!(read.kernel(), boot_image.kernel());
+    assert_eq!(read.ramdisk(), boot_image.ramdisk());
+    assert_eq!(&read.header().magic, b"NOTANIMG");
+
+    let bytes = with_page_size_field(&boot_image, 0xFFFFFFFF);
+    let read = BootImage::read_from_opts(&mut Cursor::new(&bytes), None, &force).unwrap();
+    assert_eq!(read.page_size(), 2048);
+    assert!(read.sections_eq(&boot_image));
+}
@@ -0,0 +1,77 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImageBuilder, QcdtEntry, QcdtError, parse_qcdt};
+
+/// Builds a version 1 table with two entries, followed by their blobs.
+fn two_entry_table() -> Vec<u8> {
+    let mut table = b"QCDT".to_vec();
+    let mut push = |value: u32| table.extend_from_slice(&value.to_le_bytes());
+    push(1);
+    push(2);
+    // Platform id, variant id, SoC revision, offset and size of every entry.
+    for &(platform_id, offset, size) in &[(0x41, 52, 4), (0x42, 56, 2)] {
+        push(platform_id);
+        push(8);
+        push(0x10000);
+        push(offset);
+        push(size);
+    }
+    table.extend_from_slice(&[0xD0, 0x0D, 0xFE, 0xED, 0xAA, 0xBB]);
+    table
+}
+
+#[test]
+fn parse_two_entries() {
+    let table = two_entry_table();
+    let entries = parse_qcdt(&table).unwrap();
+    assert_eq!(
+        entries,
+        vec![
+            QcdtEntry {
+                platform_id: 0x41,
+                variant_id: 8,
+                subtype_id: 0,
+                soc_rev: 0x10000,
+                pmic_rev: [0; 4],
+                offset: 52,
+                size: 4,
+            },
+            QcdtEntry {
+                platform_id: 0x42,
+                variant_id: 8,
+                subtype_id: 0,
+                soc_rev: 0x10000,
+                pmic_rev: [0; 4],
+                offset: 56,
+                size: 2,
+            },
+        ]
+    );
+    assert_eq!(entries[0].dtb(&table), Some(&[0xD0, 0x0D, 0xFE, 0xED][..]));
+    assert_eq!(entries[1].dtb(&table), Some(&[0xAA, 0xBB][..]));
+}
+
+#[test]
+fn dt_entries() {
+    let mut boot_image = BootImageBuilder::new().kernel(vec![1; 100]).build().unwrap();
+    boot_image.insert_device_tree(two_entry_table());
+    assert_eq!(boot_image.dt_entries().unwrap().len(), 2);
+}
+
+#[test]
+fn invalid_tables() {
+    match parse_qcdt(b"DTBH") {
+        Err(QcdtError::BadMagic) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match parse_qcdt(&two_entry_table()[..30]) {
+        Err(QcdtError::Truncated) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let mut table = two_entry_table();
+    table[4] = 9;
+    match parse_qcdt(&table) {
+        Err(QcdtError::UnsupportedVersion(9)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,63 @@
+extern crate android_bootimage;
+
+use android_bootimage::{BootImageBuilder, KernelFormat, ParseSectionKindError, SecondStageKind,
+                        SectionKind};
+
+#[test]
+fn second_stage_kind() {
+    assert_eq!(SecondStageKind::detect(b"\x7FELF\x01\x01"), SecondStageKind::Bootloader);
+    assert_eq!(SecondStageKind::detect(b"070701000000"), SecondStageKind::Ramdisk);
+    assert_eq!(SecondStageKind::detect(&[0x1F, 0x8B, 0x08, 0x00]), SecondStageKind::Ramdisk);
+    assert_eq!(SecondStageKind::detect(b"something"), SecondStageKind::Unknown);
+
+    let mut boot_image = BootImageBuilder::new().kernel(vec![1; 100]).build().unwrap();
+    assert_eq!(boot_image.second_stage_kind(), None);
+    boot_image.insert_second_ramdisk(b"\x7FELF\x02\x01\x01".to_vec());
+    assert_eq!(boot_image.second_stage_kind(), Some(SecondStageKind::Bootloader));
+}
+
+/// Returns a kernel header of 64 bytes, with the given bytes at its start and
+/// at the given offset.
+fn kernel_header(start: &[u8], offset: usize, magic: &[u8]) -> Vec<u8> {
+    let mut kernel = vec![0; 64];
+    kernel[..start.len()].copy_from_slice(start);
+    kernel[offset..offset + magic.len()].copy_from_slice(magic);
+    kernel
+}
+
+#[test]
+fn kernel_format() {
+    let efi = kernel_header(b"MZ", 0x38, b"ARM\x64");
+    assert_eq!(KernelFormat::detect(&efi), KernelFormat::Arm64Efi);
+    let image = kernel_header(b"", 0x38, b"ARM\x64");
+    assert_eq!(KernelFormat::detect(&image), KernelFormat::Arm64Image);
+    let zimage = kernel_header(b"", 0x24, b"\x18\x28\x6F\x01");
+    assert_eq!(KernelFormat::detect(&zimage), KernelFormat::Arm32Zimage);
+    let gzip = kernel_header(&[0x1F, 0x8B, 0x08, 0x00], 0, b"");
+    assert_eq!(KernelFormat::detect(&gzip), KernelFormat::Gzip);
+    assert_eq!(KernelFormat::detect(b"MZ"), KernelFormat::Unknown);
+    assert_eq!(KernelFormat::detect(&[]), KernelFormat::Unknown);
+
+    let boot_image = BootImageBuilder::new().kernel(efi).build().unwrap();
+    assert_eq!(boot_image.kernel_format(), KernelFormat::Arm64Efi);
+}
+
+#[test]
+fn section_names_round_trip() {
+    for &section in &[
+        SectionKind::Header,
+        SectionKind::Kernel,
+        SectionKind::Ramdisk,
+        SectionKind::Second,
+        SectionKind::DeviceTree,
+    ] {
+        assert_eq!(section.name().parse::<SectionKind>().unwrap(), section);
+    }
+
+    for name in &["", "Kernel", "dtb", "second "] {
+        match name.parse::<SectionKind>() {
+            Err(ParseSectionKindError::UnknownSection(ref unknown)) if unknown == name => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "serde")]
+
+extern crate android_bootimage;
+extern crate serde_json;
+
+use android_bootimage::{BootImageBuilder, HEADER_SIZE, Header};
+
+#[test]
+fn round_trip() {
+    let boot_image = BootImageBuilder::new()
+        .kernel(vec![1; 3000])
+        .cmdline("console=ttyS0")
+        .product_name("test")
+        .build()
+        .unwrap();
+    let mut header = boot_image.header().clone();
+    header.unique_id = boot_image.compute_id();
+
+    let json = serde_json::to_string(&header).unwrap();
+    let deserialized: Header = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, header);
+}
+
+#[test]
+fn round_trip_full_length_strings() {
+    let mut bytes = [0; HEADER_SIZE];
+    Header::default().write_to(&mut &mut bytes[..]).unwrap();
+    // The product name is stored at offset 48, followed by the command line.
+    for byte in &mut bytes[48..48 + 24 + 512] {
+        *byte = b'a';
+    }
+    let header = Header::parse(&bytes);
+    assert_eq!(header.product_name_str().len(), 24);
+    assert_eq!(header.cmdline().len(), 512);
+
+    let json = serde_json::to_string(&header).unwrap();
+    let deserialized: Header = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, header);
+}
+
+#[test]
+fn rejects_oversized_strings() {
+    let json = serde_json::to_string(&Header::default()).unwrap();
+    let product_name = format!("\"product_name\":\"{}\"", "a".repeat(25));
+    let json = json.replace("\"product_name\":\"\"", &product_name);
+    assert!(serde_json::from_str::<Header>(&json).is_err());
+}